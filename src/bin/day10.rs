@@ -1,16 +1,101 @@
 use aoc::io::*;
+use aoc::result::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::thread;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+// The register names, X is the register the CRT reads from.
+const REGISTERS: [&str; 4] = ["x", "y", "z", "w"];
+const X: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Imm(i32),
+    Reg(usize),
+}
+
+// Jump targets are absolute instruction indices.
+#[derive(Debug, Clone, PartialEq)]
 enum Instruction {
     Noop,
     Addx(i32),
+    Set(usize, Operand),
+    Add(usize, Operand),
+    Sub(usize, Operand),
+    Mul(usize, Operand),
+    Jmp(usize),
+    Jz(usize, usize),
+    Jnz(usize, usize),
+    Halt,
 }
 
 impl Instruction {
-    fn cycles(&self) -> usize {
+    fn mnemonic(&self) -> &'static str {
+        match *self {
+            Instruction::Noop => "noop",
+            Instruction::Addx(_) => "addx",
+            Instruction::Set(..) => "set",
+            Instruction::Add(..) => "add",
+            Instruction::Sub(..) => "sub",
+            Instruction::Mul(..) => "mul",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Jz(..) => "jz",
+            Instruction::Jnz(..) => "jnz",
+            Instruction::Halt => "halt",
+        }
+    }
+
+    fn target(&self) -> Option<usize> {
         match *self {
-            Instruction::Noop => 1,
-            Instruction::Addx(_) => 2,
+            Instruction::Jmp(target) | Instruction::Jz(_, target) | Instruction::Jnz(_, target) => {
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+}
+
+// The number of cycles each instruction takes to complete, by mnemonic.
+#[derive(Debug, Clone)]
+struct CycleCosts(HashMap<&'static str, usize>);
+
+impl Default for CycleCosts {
+    fn default() -> Self {
+        CycleCosts(HashMap::from([
+            ("noop", 1),
+            ("addx", 2),
+            ("set", 1),
+            ("add", 2),
+            ("sub", 2),
+            ("mul", 2),
+            ("jmp", 1),
+            ("jz", 1),
+            ("jnz", 1),
+            ("halt", 1),
+        ]))
+    }
+}
+
+impl CycleCosts {
+    fn get(&self, instruction: &Instruction) -> usize {
+        self.0[instruction.mnemonic()]
+    }
+
+    // Updates the cost of an instruction, every instruction takes at least
+    // one cycle.
+    fn set(&mut self, mnemonic: &str, cycles: usize) -> AocResult<()> {
+        match self.0.get_mut(mnemonic) {
+            Some(cost) if cycles > 0 => {
+                *cost = cycles;
+                Ok(())
+            }
+            _ => parse_error(
+                format!("{mnemonic}={cycles}"),
+                "<mnemonic>=<cycles> with at least one cycle".into(),
+            ),
         }
     }
 }
@@ -18,52 +103,109 @@ impl Instruction {
 // An executbale program
 #[derive(Debug)]
 struct Program {
-    // current register value
-    register: i32,
+    // current register values
+    registers: [i32; REGISTERS.len()],
     // the current cycle
     cycle: i32,
     // the program counter
     counter: usize,
     // number of cycles until current instruction completes
     delay: usize,
-    // true when the program is finished executing
-    done: bool,
+    // the cycle cost of each instruction
+    costs: CycleCosts,
     // the program instructions
     instructions: Vec<Instruction>,
 }
 
 impl Program {
-    fn new(instructions: Vec<Instruction>) -> Program {
-        Program {
-            register: 1,
+    // Creates a program, checking that every register and jump target it
+    // refers to exists. Jumping to the end of the program halts it.
+    fn new(instructions: Vec<Instruction>, costs: CycleCosts) -> AocResult<Program> {
+        for instruction in instructions.iter() {
+            let register = match *instruction {
+                Instruction::Set(r, v)
+                | Instruction::Add(r, v)
+                | Instruction::Sub(r, v)
+                | Instruction::Mul(r, v) => match v {
+                    Operand::Reg(r2) => Some(r.max(r2)),
+                    Operand::Imm(_) => Some(r),
+                },
+                Instruction::Jz(r, _) | Instruction::Jnz(r, _) => Some(r),
+                _ => None,
+            };
+            let valid_register = register.is_none_or(|r| r < REGISTERS.len());
+            let valid_target = instruction
+                .target()
+                .is_none_or(|target| target <= instructions.len());
+            if !valid_register || !valid_target {
+                let expected = "existing registers and jump targets within the program";
+                return parse_error(format!("{instruction:?}"), expected.into());
+            }
+        }
+        let mut registers = [0; REGISTERS.len()];
+        registers[X] = 1;
+        Ok(Program {
+            registers,
             cycle: 1,
             counter: 0,
-            delay: instructions[0].cycles(),
-            done: false,
+            delay: instructions.first().map_or(0, |first| costs.get(first)),
+            costs,
             instructions,
+        })
+    }
+
+    // True once the program counter moves past the last instruction (which
+    // is immediately the case for an empty program).
+    fn halted(&self) -> bool {
+        self.counter >= self.instructions.len()
+    }
+
+    fn x(&self) -> i32 {
+        self.registers[X]
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Imm(n) => n,
+            Operand::Reg(r) => self.registers[r],
         }
     }
 
     fn step(&mut self) {
-        if self.done {
+        if self.halted() {
             return;
         }
 
         self.delay -= 1;
         if self.delay == 0 {
-            // execute operation
+            // execute operation, moving on to the next instruction
+            // unless we jump. Arithmetic wraps around on overflow, like
+            // a machine word.
+            let mut next = self.counter + 1;
             match self.instructions[self.counter] {
                 Instruction::Noop => (),
-                Instruction::Addx(n) => self.register += n,
+                Instruction::Addx(n) => self.registers[X] = self.registers[X].wrapping_add(n),
+                Instruction::Set(r, v) => self.registers[r] = self.value(v),
+                Instruction::Add(r, v) => {
+                    self.registers[r] = self.registers[r].wrapping_add(self.value(v))
+                }
+                Instruction::Sub(r, v) => {
+                    self.registers[r] = self.registers[r].wrapping_sub(self.value(v))
+                }
+                Instruction::Mul(r, v) => {
+                    self.registers[r] = self.registers[r].wrapping_mul(self.value(v))
+                }
+                Instruction::Jmp(target) => next = target,
+                Instruction::Jz(r, target) if self.registers[r] == 0 => next = target,
+                Instruction::Jnz(r, target) if self.registers[r] != 0 => next = target,
+                Instruction::Jz(..) | Instruction::Jnz(..) => (),
+                Instruction::Halt => next = self.instructions.len(),
             }
             // update program counter
-            self.counter += 1;
+            self.counter = next;
             // setup next operation
-            if self.counter < self.instructions.len() {
-                let next = &self.instructions[self.counter];
-                self.delay = next.cycles();
-            } else {
-                self.done = true;
+            if let Some(next) = self.instructions.get(self.counter) {
+                self.delay = self.costs.get(next);
             }
         }
         // increment the cycle
@@ -71,53 +213,919 @@ impl Program {
     }
 }
 
-fn parse_instructions(input: &Vec<String>) -> Vec<Instruction> {
-    input.iter().fold(Vec::new(), |mut instructions, line| {
-        let parts: Vec<&str> = line.as_str().split(" ").collect();
-        match parts[..] {
-            ["addx", n] => {
-                let value = n.parse().unwrap();
-                instructions.push(Instruction::Addx(value));
+fn parse_register((n, text): (usize, &str)) -> AocResult<usize> {
+    match REGISTERS.iter().position(|r| *r == text) {
+        Some(r) => Ok(r),
+        None => parse_error_at(n, text.to_string(), format!("a register {REGISTERS:?}")),
+    }
+}
+
+fn parse_int((n, text): (usize, &str)) -> AocResult<i32> {
+    match text.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => parse_error_at(n, text.to_string(), "an integer".into()),
+    }
+}
+
+// Parses an integer given outside of a program (in a debugger command or on
+// the command line), where there is no line to point at.
+fn parse_int_arg(text: &str) -> AocResult<i32> {
+    match text.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => parse_error(text.to_string(), "an integer".into()),
+    }
+}
+
+fn parse_register_arg(text: &str) -> AocResult<usize> {
+    match REGISTERS.iter().position(|r| *r == text) {
+        Some(r) => Ok(r),
+        None => parse_error(text.to_string(), format!("a register {REGISTERS:?}")),
+    }
+}
+
+fn parse_operand((n, text): (usize, &str)) -> AocResult<Operand> {
+    match parse_register((n, text)) {
+        Ok(r) => Ok(Operand::Reg(r)),
+        Err(_) => match parse_int((n, text)) {
+            Ok(value) => Ok(Operand::Imm(value)),
+            Err(_) => parse_error_at(n, text.to_string(), "a register or an integer".into()),
+        },
+    }
+}
+
+// Strips comments and splits off any `label:` prefix from a line.
+fn split_label(line: &str) -> (Option<&str>, &str) {
+    let line = line.split('#').next().unwrap_or("").trim();
+    match line.split_once(':') {
+        Some((label, rest)) => (Some(label.trim()), rest.trim()),
+        None => (None, line),
+    }
+}
+
+// Assembles a program, one instruction per line. Lines may start with a
+// `label:` which jump instructions can target, and `#` starts a comment.
+//
+//   set y 3
+//   loop: addx 2
+//   sub y 1
+//   jnz y loop
+fn assemble(input: &[String]) -> AocResult<Vec<Instruction>> {
+    // first pass: find the instruction index of every label
+    let mut labels = HashMap::new();
+    let mut index = 0;
+    for (i, line) in input.iter().enumerate() {
+        let (label, rest) = split_label(line);
+        if let Some(label) = label {
+            let valid = !label.is_empty() && !label.contains(char::is_whitespace);
+            if !valid || labels.insert(label, index).is_some() {
+                return parse_error_at(i + 1, line.to_string(), "a unique label".into());
             }
-            ["noop"] => instructions.push(Instruction::Noop),
-            _ => panic!(),
         }
-        instructions
-    })
+        if !rest.is_empty() {
+            index += 1;
+        }
+    }
+
+    // second pass: assemble the instructions
+    let mut instructions = Vec::with_capacity(index);
+    for (i, line) in input.iter().enumerate() {
+        let n = i + 1;
+        let (_, rest) = split_label(line);
+        let target = |label: &str| match labels.get(label) {
+            Some(&target) => Ok(target),
+            None => parse_error_at(n, label.to_string(), "a defined label".into()),
+        };
+        let parts: Vec<&str> = rest.split_whitespace().collect();
+        let instruction = match parts[..] {
+            [] => continue,
+            ["noop"] => Instruction::Noop,
+            ["addx", v] => Instruction::Addx(parse_int((n, v))?),
+            ["set", r, v] => Instruction::Set(parse_register((n, r))?, parse_operand((n, v))?),
+            ["add", r, v] => Instruction::Add(parse_register((n, r))?, parse_operand((n, v))?),
+            ["sub", r, v] => Instruction::Sub(parse_register((n, r))?, parse_operand((n, v))?),
+            ["mul", r, v] => Instruction::Mul(parse_register((n, r))?, parse_operand((n, v))?),
+            ["jmp", label] => Instruction::Jmp(target(label)?),
+            ["jz", r, label] => Instruction::Jz(parse_register((n, r))?, target(label)?),
+            ["jnz", r, label] => Instruction::Jnz(parse_register((n, r))?, target(label)?),
+            ["halt"] => Instruction::Halt,
+            _ => return parse_error_at(n, line.to_string(), "an instruction".into()),
+        };
+        instructions.push(instruction);
+    }
+    Ok(instructions)
 }
 
-fn part1(instructions: Vec<Instruction>) -> i32 {
-    let mut program = Program::new(instructions);
-    let mut signal_strength = 0;
-    while !program.done {
-        if program.cycle == 20 || (program.cycle > 20 && (program.cycle - 20) % 40 == 0) {
-            signal_strength += program.register * program.cycle;
+// Formats an instruction as assembly, with jump targets named `L<index>`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let register = |r: usize| REGISTERS[r];
+        let operand = |v: Operand| match v {
+            Operand::Imm(n) => n.to_string(),
+            Operand::Reg(r) => register(r).to_string(),
+        };
+        let mnemonic = self.mnemonic();
+        match *self {
+            Instruction::Noop | Instruction::Halt => write!(f, "{mnemonic}"),
+            Instruction::Addx(n) => write!(f, "{mnemonic} {n}"),
+            Instruction::Set(r, v)
+            | Instruction::Add(r, v)
+            | Instruction::Sub(r, v)
+            | Instruction::Mul(r, v) => write!(f, "{mnemonic} {} {}", register(r), operand(v)),
+            Instruction::Jmp(t) => write!(f, "{mnemonic} L{t}"),
+            Instruction::Jz(r, t) | Instruction::Jnz(r, t) => {
+                write!(f, "{mnemonic} {} L{t}", register(r))
+            }
         }
-        program.step();
     }
-    signal_strength
 }
 
-fn part2(instructions: Vec<Instruction>) {
-    let mut program = Program::new(instructions);
-    while !program.done {
-        let col = (program.cycle - 1) % 40;
-        if program.register - 1 <= col && col <= program.register + 1 {
-            print!("#");
-        } else {
-            print!(".");
+// Turns a program back into assembly, naming every jump target `L<index>`.
+fn disassemble(instructions: &[Instruction]) -> String {
+    let targets: Vec<usize> = instructions
+        .iter()
+        .filter_map(Instruction::target)
+        .collect();
+    let mut res = String::new();
+    for i in 0..=instructions.len() {
+        if targets.contains(&i) {
+            res += &format!("L{i}:\n");
         }
-        if program.cycle % 40 == 0 {
-            println!();
+        if let Some(instruction) = instructions.get(i) {
+            res += &format!("  {instruction}\n");
+        }
+    }
+    res
+}
+
+// An integer expression over the program state, e.g. `x * cycle`.
+#[derive(Debug, Clone)]
+enum Expr {
+    Int(i32),
+    Reg(usize),
+    Cycle,
+    Counter,
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // Returns None if the value (or any part of it) overflows an i32.
+    fn eval(&self, program: &Program) -> Option<i32> {
+        match self {
+            Expr::Int(n) => Some(*n),
+            Expr::Reg(r) => Some(program.registers[*r]),
+            Expr::Cycle => Some(program.cycle),
+            Expr::Counter => i32::try_from(program.counter).ok(),
+            Expr::Add(a, b) => a.eval(program)?.checked_add(b.eval(program)?),
+            Expr::Sub(a, b) => a.eval(program)?.checked_sub(b.eval(program)?),
+            Expr::Mul(a, b) => a.eval(program)?.checked_mul(b.eval(program)?),
+        }
+    }
+}
+
+// Splits an expression into numbers, names, operators and parentheses.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut prev = ' ';
+    for c in text.chars() {
+        if c.is_whitespace() {
+            prev = ' ';
+            continue;
+        }
+        let continues_word = c.is_alphanumeric() && prev.is_alphanumeric();
+        let continues_op = "<>=!".contains(prev) && c == '=';
+        match tokens.last_mut() {
+            Some(token) if continues_word || continues_op => token.push(c),
+            _ => tokens.push(c.to_string()),
+        }
+        prev = c;
+    }
+    tokens
+}
+
+// A recursive descent parser for expressions, where `*` binds tighter than
+// `+` and `-`:
+//
+//   expr = term (("+" | "-") term)*
+//   term = atom ("*" atom)*
+//   atom = integer | register | "cycle" | "pc" | "(" expr ")"
+struct ExprParser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> AocResult<String> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => parse_error(self.tokens.join(" "), "a complete expression".into()),
+        }
+    }
+
+    fn expr(&mut self) -> AocResult<Expr> {
+        let mut expr = self.term()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let add = op == "+";
+            self.pos += 1;
+            let (a, b) = (Box::new(expr), Box::new(self.term()?));
+            expr = if add {
+                Expr::Add(a, b)
+            } else {
+                Expr::Sub(a, b)
+            };
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> AocResult<Expr> {
+        let mut expr = self.atom()?;
+        while let Some("*") = self.peek() {
+            self.pos += 1;
+            expr = Expr::Mul(Box::new(expr), Box::new(self.atom()?));
+        }
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> AocResult<Expr> {
+        let token = self.next()?;
+        match token.as_str() {
+            "(" => {
+                let expr = self.expr()?;
+                match self.next()?.as_str() {
+                    ")" => Ok(expr),
+                    got => parse_error(got.to_string(), "')'".into()),
+                }
+            }
+            "-" => Ok(Expr::Sub(Box::new(Expr::Int(0)), Box::new(self.atom()?))),
+            "cycle" => Ok(Expr::Cycle),
+            "pc" => Ok(Expr::Counter),
+            name => match (REGISTERS.iter().position(|r| *r == name), name.parse()) {
+                (Some(r), _) => Ok(Expr::Reg(r)),
+                (None, Ok(n)) => Ok(Expr::Int(n)),
+                _ => parse_error(token, "an integer, a register, cycle or pc".into()),
+            },
+        }
+    }
+}
+
+fn parse_expr(text: &str) -> AocResult<Expr> {
+    let mut parser = ExprParser {
+        tokens: tokenize(text),
+        pos: 0,
+    };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => parse_error(token.to_string(), "the end of the expression".into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Eq,
+    Ne,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+// The comparison operators, with the two character ones first so that `<=`
+// isn't taken for `<`.
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Eq),
+    ("!=", Comparison::Ne),
+    ("<=", Comparison::Le),
+    (">=", Comparison::Ge),
+    ("<", Comparison::Lt),
+    (">", Comparison::Gt),
+];
+
+impl Comparison {
+    fn holds(&self, a: i32, b: i32) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::Le => a <= b,
+            Comparison::Ge => a >= b,
+            Comparison::Lt => a < b,
+            Comparison::Gt => a > b,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Breakpoint {
+    Cycle(i32),
+    Counter(usize),
+    // stops when the comparison of two expressions holds
+    When(Expr, Comparison, Expr),
+}
+
+impl Breakpoint {
+    fn hit(&self, program: &Program) -> bool {
+        match self {
+            Breakpoint::Cycle(cycle) => program.cycle == *cycle,
+            Breakpoint::Counter(counter) => program.counter == *counter,
+            // a comparison that overflows never holds
+            Breakpoint::When(a, op, b) => match (a.eval(program), b.eval(program)) {
+                (Some(a), Some(b)) => op.holds(a, b),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn parse_breakpoint(text: &str) -> AocResult<Breakpoint> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    match parts[..] {
+        ["cycle", n] => Ok(Breakpoint::Cycle(parse_int_arg(n)?)),
+        ["pc", n] => Ok(Breakpoint::Counter(parse_int_arg(n)? as usize)),
+        _ => {
+            for (symbol, op) in COMPARISONS {
+                if let Some((a, b)) = text.split_once(symbol) {
+                    return Ok(Breakpoint::When(parse_expr(a)?, op, parse_expr(b)?));
+                }
+            }
+            let expected = "cycle <n>, pc <n> or <expr> {==,!=,<,<=,>,>=} <expr>";
+            parse_error(text.to_string(), expected.into())
+        }
+    }
+}
+
+// A step debugger for a program. Every step is one cycle, the instruction
+// shown for a cycle is the one in progress during that cycle.
+struct Debugger {
+    program: Program,
+    breakpoints: Vec<Breakpoint>,
+    // the watched expressions, along with their source text
+    watches: Vec<(String, Expr)>,
+    trace: bool,
+}
+
+impl Debugger {
+    fn new(program: Program) -> Debugger {
+        Debugger {
+            program,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            trace: false,
+        }
+    }
+
+    // The `cycle / pc / instruction / X` state of the program.
+    fn state(&self) -> String {
+        let program = &self.program;
+        match program.instructions.get(program.counter) {
+            Some(instruction) => format!(
+                "{:>6} / {:>4} / {:<12} / {}",
+                program.cycle,
+                program.counter,
+                instruction.to_string(),
+                program.x()
+            ),
+            _ => format!("{:>6} / halted", program.cycle),
+        }
+    }
+
+    fn step(&mut self) {
+        if self.trace {
+            println!("{}", self.state());
+        }
+        self.program.step();
+    }
+
+    // Runs until a breakpoint is hit or the program halts, returning the
+    // index of the breakpoint that was hit. A breakpoint is only hit when it
+    // goes from not holding to holding, so a condition that stays true (or a
+    // multi-cycle instruction) doesn't stop every cycle.
+    fn resume(&mut self) -> Option<usize> {
+        while !self.program.halted() {
+            let before: Vec<bool> = self
+                .breakpoints
+                .iter()
+                .map(|b| b.hit(&self.program))
+                .collect();
+            self.step();
+            let hit = self
+                .breakpoints
+                .iter()
+                .zip(before)
+                .position(|(b, before)| !before && b.hit(&self.program));
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        None
+    }
+
+    fn print_watches(&self) {
+        for (text, expr) in self.watches.iter() {
+            match expr.eval(&self.program) {
+                Some(value) => println!("  {text} = {value}"),
+                None => println!("  {text} overflows"),
+            }
+        }
+    }
+
+    // Runs a single debugger command, returning false on quit.
+    fn command(&mut self, line: &str) -> AocResult<bool> {
+        let (command, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let rest = rest.trim();
+        match command {
+            "" => (),
+            "break" | "b" => {
+                self.breakpoints.push(parse_breakpoint(rest)?);
+                println!("breakpoint #{}: {rest}", self.breakpoints.len() - 1);
+            }
+            "delete" | "d" => {
+                let i = parse_int_arg(rest)? as usize;
+                if i >= self.breakpoints.len() {
+                    return parse_error(rest.to_string(), "a breakpoint number".into());
+                }
+                self.breakpoints.remove(i);
+            }
+            "watch" | "w" => self.watches.push((rest.to_string(), parse_expr(rest)?)),
+            "step" | "s" => {
+                let n = if rest.is_empty() {
+                    1
+                } else {
+                    parse_int_arg(rest)?
+                };
+                for _ in 0..n {
+                    self.step();
+                }
+                println!("{}", self.state());
+                self.print_watches();
+            }
+            "continue" | "c" => {
+                match self.resume() {
+                    Some(i) => println!("breakpoint #{i}"),
+                    None => println!("program halted"),
+                }
+                println!("{}", self.state());
+                self.print_watches();
+            }
+            "print" | "p" => match parse_expr(rest)?.eval(&self.program) {
+                Some(value) => println!("{rest} = {value}"),
+                None => return parse_error(rest.to_string(), "a value that fits an i32".into()),
+            },
+            "regs" => {
+                for (name, value) in REGISTERS.iter().zip(self.program.registers) {
+                    println!("  {name} = {value}");
+                }
+            }
+            "trace" => match rest {
+                "on" => self.trace = true,
+                "off" => self.trace = false,
+                _ => return parse_error(rest.to_string(), "on or off".into()),
+            },
+            "quit" | "q" => return Ok(false),
+            _ => {
+                let expected = "break, delete, watch, step, continue, print, regs, trace or quit";
+                return parse_error(command.to_string(), expected.into());
+            }
+        }
+        Ok(true)
+    }
+
+    // Runs commands from a script, or from the terminal when it's
+    // interactive (e.g. `--debug /dev/tty`).
+    fn run(&mut self, commands: impl BufRead, interactive: bool) {
+        println!("{}", self.state());
+        if interactive {
+            print!("(dbg) ");
+            io::stdout().flush().unwrap();
+        }
+        for line in commands.lines() {
+            let line = line.unwrap();
+            if !interactive {
+                println!("(dbg) {line}");
+            }
+            match self.command(&line) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => println!("error: {e:?}"),
+            }
+            if interactive {
+                print!("(dbg) ");
+                io::stdout().flush().unwrap();
+            }
+        }
+    }
+}
+
+// The cycles at which to sample a register.
+enum Schedule {
+    At(Vec<i32>),
+    Every { start: i32, stride: i32 },
+    When(Box<dyn Fn(i32) -> bool>),
+}
+
+impl Schedule {
+    fn includes(&self, cycle: i32) -> bool {
+        match self {
+            Schedule::At(cycles) => cycles.contains(&cycle),
+            Schedule::Every { start, stride } => cycle >= *start && (cycle - start) % stride == 0,
+            Schedule::When(predicate) => predicate(cycle),
+        }
+    }
+}
+
+// Parses a schedule given as `all`, a list of cycles (`20,60,100`) or a start
+// and stride (`20+40`).
+fn parse_schedule(text: &str) -> AocResult<Schedule> {
+    if text == "all" {
+        return Ok(Schedule::When(Box::new(|_| true)));
+    }
+    if let Some((start, stride)) = text.split_once('+') {
+        let (start, stride) = (parse_int_arg(start)?, parse_int_arg(stride)?);
+        if stride <= 0 {
+            return parse_error(text.to_string(), "a stride of at least 1".into());
+        }
+        return Ok(Schedule::Every { start, stride });
+    }
+    let cycles = text.split(',').map(parse_int_arg);
+    Ok(Schedule::At(cycles.collect::<AocResult<_>>()?))
+}
+
+// Runs the program to completion, returning the (cycle, value) of the
+// register during every scheduled cycle.
+fn sample(program: &mut Program, schedule: &Schedule, register: usize) -> Vec<(i32, i32)> {
+    let mut samples = Vec::new();
+    while !program.halted() {
+        if schedule.includes(program.cycle) {
+            samples.push((program.cycle, program.registers[register]));
         }
         program.step();
     }
+    samples
+}
+
+fn signal_strength(samples: &[(i32, i32)]) -> i32 {
+    samples.iter().map(|(cycle, x)| cycle * x).sum()
+}
+
+fn part1(instructions: Vec<Instruction>, costs: CycleCosts) -> AocResult<i32> {
+    let mut program = Program::new(instructions, costs)?;
+    let schedule = Schedule::Every {
+        start: 20,
+        stride: 40,
+    };
+    Ok(signal_strength(&sample(&mut program, &schedule, X)))
+}
+
+// The letters of the 4x6 font drawn on the CRT.
+const FONT: [(char, [&str; 6]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+// Reads the letters off a rendered screen. Letters are 4 pixels wide with a
+// blank column between them, blank space is skipped.
+fn ocr(rows: &[String]) -> AocResult<String> {
+    if rows.len() != 6 {
+        return parse_error(rows.join("\n"), "6 rows of pixels".into());
+    }
+    let width = rows.iter().map(String::len).min().unwrap_or(0);
+    let mut letters = String::new();
+    for x in (0..width.saturating_sub(3)).step_by(5) {
+        let glyph: Vec<&str> = rows.iter().map(|row| &row[x..x + 4]).collect();
+        if glyph.iter().all(|pixels| !pixels.contains('#')) {
+            continue;
+        }
+        match FONT.iter().find(|(_, pixels)| glyph == pixels) {
+            Some((letter, _)) => letters.push(*letter),
+            None => return parse_error(glyph.join("\n"), "a letter of the 4x6 font".into()),
+        }
+    }
+    Ok(letters)
+}
+
+// A CRT framebuffer, drawn one pixel per cycle from left to right and top to
+// bottom (wrapping around to the first pixel after the last one). A pixel is
+// lit when it overlaps the sprite, which is centered on register X.
+#[derive(Debug, Clone)]
+struct Crt {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+    pixels: Vec<bool>,
+}
+
+impl Crt {
+    fn new(width: usize, height: usize, sprite_width: usize) -> AocResult<Crt> {
+        if width == 0 || height == 0 {
+            return parse_error(
+                format!("{width}x{height}"),
+                "a screen of at least 1x1".into(),
+            );
+        }
+        Ok(Crt {
+            width,
+            height,
+            sprite_width,
+            pixels: vec![false; width * height],
+        })
+    }
+
+    // Draws the pixel for the program's current cycle.
+    fn draw(&mut self, program: &Program) {
+        let i = (program.cycle - 1) as usize % self.pixels.len();
+        let col = (i % self.width) as i32;
+        let sprite_start = program.x() - (self.sprite_width as i32 - 1) / 2;
+        let sprite_end = sprite_start + self.sprite_width as i32 - 1;
+        self.pixels[i] = sprite_start <= col && col <= sprite_end;
+    }
+
+    // Drives the program to completion, drawing a pixel every cycle and
+    // calling `on_cycle` once each pixel is drawn.
+    fn run(&mut self, program: &mut Program, mut on_cycle: impl FnMut(&Crt)) {
+        while !program.halted() {
+            self.draw(program);
+            on_cycle(self);
+            program.step();
+        }
+    }
+
+    fn rows(&self) -> Vec<String> {
+        self.pixels
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&lit| if lit { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        self.rows().iter().map(|row| format!("{row}\n")).collect()
+    }
+
+    // A plain (P1) PBM image, with lit pixels drawn in black.
+    fn to_pbm(&self) -> String {
+        let mut res = format!("P1\n{} {}\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width) {
+            let bits: Vec<&str> = row.iter().map(|&lit| if lit { "1" } else { "0" }).collect();
+            res += &bits.join(" ");
+            res.push('\n');
+        }
+        res
+    }
+
+    // An 8-bit grayscale PNG image, with lit pixels drawn in black and each
+    // pixel scaled up to a `scale` x `scale` square. The image data is stored
+    // uncompressed, so no deflate implementation is needed.
+    fn to_png(&self, scale: usize) -> AocResult<Vec<u8>> {
+        if scale == 0 {
+            return parse_error(scale.to_string(), "a scale of at least 1".into());
+        }
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut raw = Vec::with_capacity((width + 1) * height);
+        for y in 0..height {
+            // filter type: none
+            raw.push(0);
+            for x in 0..width {
+                let lit = self.pixels[(y / scale) * self.width + x / scale];
+                raw.push(if lit { 0 } else { 255 });
+            }
+        }
+
+        // zlib stream made of stored deflate blocks
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(u16::MAX as usize).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            let len = block.len() as u16;
+            zlib.push((i == blocks.len() - 1) as u8);
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(*block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::new();
+        header.extend((width as u32).to_be_bytes());
+        header.extend((height as u32).to_be_bytes());
+        // bit depth 8, grayscale, default compression, filter and interlace
+        header.extend([8, 0, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        for (kind, data) in [(b"IHDR", header), (b"IDAT", zlib), (b"IEND", Vec::new())] {
+            png.extend((data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend(kind);
+            png.extend(data);
+            let crc = crc32(&png[start..]);
+            png.extend(crc.to_be_bytes());
+        }
+        Ok(png)
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn part2(
+    instructions: Vec<Instruction>,
+    costs: CycleCosts,
+    crt: &mut Crt,
+    animate: Option<Duration>,
+) -> AocResult<String> {
+    let mut program = Program::new(instructions, costs)?;
+    crt.run(&mut program, |crt| {
+        if let Some(delay) = animate {
+            // clear the terminal and redraw the screen
+            print!("\x1b[2J\x1b[H{}", crt.render());
+            thread::sleep(delay);
+        }
+    });
+    ocr(&crt.rows())
 }
 
+// Usage: day10 [--cost <mnemonic>=<cycles>]... [--disassemble]
+//              [--debug <script>] [--trace]
+//              [--sample <all|c1,c2,...|start+stride>] [--register <name>]
+//              [--size <width>x<height>] [--sprite <width>] [--show]
+//              [--animate <ms per cycle>] [--pbm <path>] [--png <path>]
 fn main() {
+    let mut costs = CycleCosts::default();
+    let mut show_disassembly = false;
+    let mut debug_script = None;
+    let mut trace = false;
+    let mut schedule = None;
+    let mut register = X;
+    let mut crt = Crt::new(40, 6, 3).unwrap();
+    let mut show = false;
+    let mut animate = None;
+    let mut pbm_path = None;
+    let mut png_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cost" => {
+                let cost = args.next().unwrap_or_default();
+                let (mnemonic, cycles) = cost.split_once('=').expect("--cost <mnemonic>=<cycles>");
+                costs.set(mnemonic, cycles.parse().unwrap()).unwrap();
+            }
+            "--disassemble" => show_disassembly = true,
+            "--debug" => debug_script = Some(args.next().expect("--debug <script>")),
+            "--trace" => trace = true,
+            "--sample" => {
+                let text = args.next().expect("--sample <schedule>");
+                schedule = Some(parse_schedule(&text).unwrap());
+            }
+            "--register" => {
+                let name = args.next().expect("--register <name>");
+                register = parse_register_arg(&name).unwrap();
+            }
+            "--size" => {
+                let size = args.next().unwrap_or_default();
+                let (width, height) = size.split_once('x').expect("--size <width>x<height>");
+                crt = Crt::new(
+                    width.parse().unwrap(),
+                    height.parse().unwrap(),
+                    crt.sprite_width,
+                )
+                .unwrap();
+            }
+            "--sprite" => {
+                let sprite_width = args.next().and_then(|w| w.parse().ok());
+                crt.sprite_width = sprite_width.expect("--sprite <width>");
+            }
+            "--show" => show = true,
+            "--animate" => {
+                let ms = args.next().and_then(|ms| ms.parse().ok());
+                animate = Some(Duration::from_millis(ms.expect("--animate <ms per cycle>")));
+            }
+            "--pbm" => pbm_path = Some(args.next().expect("--pbm <path>")),
+            "--png" => png_path = Some(args.next().expect("--png <path>")),
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+
     let input: Vec<String> = read_input();
-    let instructions = parse_instructions(&input);
-    println!("part 1 = {:?}", part1(instructions.clone()));
-    println!("part 2 = ");
-    part2(instructions.clone());
+    let instructions = assemble(&input).unwrap();
+    if show_disassembly {
+        print!("{}", disassemble(&instructions));
+        return;
+    }
+    if let Some(schedule) = schedule {
+        let mut program = Program::new(instructions, costs).unwrap();
+        let samples = sample(&mut program, &schedule, register);
+        println!("cycle,{}", REGISTERS[register]);
+        for (cycle, value) in samples.iter() {
+            println!("{cycle},{value}");
+        }
+        println!("signal strength = {}", signal_strength(&samples));
+        return;
+    }
+    if debug_script.is_some() || trace {
+        let mut debugger = Debugger::new(Program::new(instructions, costs).unwrap());
+        debugger.trace = trace;
+        match debug_script {
+            Some(path) => {
+                let script = File::open(&path).unwrap();
+                let interactive = script.is_terminal();
+                debugger.run(BufReader::new(script), interactive);
+            }
+            None => {
+                debugger.resume();
+            }
+        }
+        return;
+    }
+    println!(
+        "part 1 = {:?}",
+        part1(instructions.clone(), costs.clone()).unwrap()
+    );
+    let part2 = part2(instructions, costs, &mut crt, animate);
+    if show {
+        print!("{}", crt.render());
+    }
+    if let Some(path) = pbm_path {
+        fs::write(path, crt.to_pbm()).unwrap();
+    }
+    if let Some(path) = png_path {
+        fs::write(path, crt.to_png(8).unwrap()).unwrap();
+    }
+    match part2 {
+        Ok(letters) => println!("part 2 = {letters:?}"),
+        // not every screen spells letters, so show it instead
+        Err(err) => {
+            if !show {
+                print!("{}", crt.render());
+            }
+            println!("part 2 = unrecognised ({err:?})");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crt_follows_the_sprite() {
+        let input = ["noop", "addx 3", "addx -5"].map(String::from);
+        let mut program = Program::new(assemble(&input).unwrap(), CycleCosts::default()).unwrap();
+        let mut crt = Crt::new(8, 1, 3).unwrap();
+        crt.run(&mut program, |_| ());
+        assert_eq!(crt.render(), "#####...\n");
+    }
+
+    #[test]
+    fn ocr_reads_rendered_letters() {
+        let screen = "#..#..###.\n\
+                      #..#...#..\n\
+                      ####...#..\n\
+                      #..#...#..\n\
+                      #..#...#..\n\
+                      #..#..###.\n";
+        let mut crt = Crt::new(10, 6, 3).unwrap();
+        let lit = screen.chars().filter(|&c| c != '\n').map(|c| c == '#');
+        crt.pixels = lit.collect();
+        assert_eq!(crt.render(), screen);
+        assert_eq!(ocr(&crt.rows()).unwrap(), "HI");
+    }
 }
@@ -1,4 +1,9 @@
 use aoc::io::*;
+use aoc::rand::Lcg;
+use aoc::result::*;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Instant;
 
 #[derive(Debug, Clone, PartialEq)]
 enum Value {
@@ -13,14 +18,15 @@ enum Operation {
 }
 
 impl Operation {
-    fn eval(&self, old: u64) -> u64 {
+    // Returns None if the new worry level doesn't fit in a u64.
+    fn eval(&self, old: u64) -> Option<u64> {
         let eval_value = |value: &Value| match value {
             Value::Old => old,
             Value::Const(n) => *n,
         };
         match self {
-            Operation::Add(a, b) => eval_value(a) + eval_value(b),
-            Operation::Mul(a, b) => eval_value(a) * eval_value(b),
+            Operation::Add(a, b) => eval_value(a).checked_add(eval_value(b)),
+            Operation::Mul(a, b) => eval_value(a).checked_mul(eval_value(b)),
         }
     }
 }
@@ -34,129 +40,595 @@ struct Monkey {
     monkey_true: usize,
 }
 
-fn parse_items(line: &str) -> Vec<u64> {
-    let mut items = Vec::new();
-    let parts: Vec<&str> = line.split(": ").collect();
+// A line of input along with its (1-based) line number.
+type Line<'a> = (usize, &'a str);
+
+fn parse_number<T: std::str::FromStr>((n, text): Line, expected: &str) -> AocResult<T> {
+    match text.trim().parse() {
+        Ok(value) => Ok(value),
+        Err(_) => parse_error_at(n, text.to_string(), expected.into()),
+    }
+}
+
+fn parse_items((n, text): Line) -> AocResult<Vec<u64>> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse_number((n, item), "a worry level"))
+        .collect()
+}
+
+fn parse_value((n, text): Line) -> AocResult<Value> {
+    match text {
+        "old" => Ok(Value::Old),
+        _ => Ok(Value::Const(parse_number((n, text), "old or a number")?)),
+    }
+}
+
+fn parse_operation((n, text): Line) -> AocResult<Operation> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    match parts[..] {
+        ["new", "=", left, "+", right] => Ok(Operation::Add(
+            parse_value((n, left))?,
+            parse_value((n, right))?,
+        )),
+        ["new", "=", left, "*", right] => Ok(Operation::Mul(
+            parse_value((n, left))?,
+            parse_value((n, right))?,
+        )),
+        _ => parse_error_at(n, text.to_string(), "new = <value> {+,*} <value>".into()),
+    }
+}
+
+fn parse_divisor((n, text): Line) -> AocResult<u64> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    match parts[..] {
+        ["divisible", "by", d] => match parse_number((n, d), "a non-zero divisor")? {
+            0 => parse_error_at(n, text.to_string(), "a non-zero divisor".into()),
+            divisor => Ok(divisor),
+        },
+        _ => parse_error_at(n, text.to_string(), "divisible by <number>".into()),
+    }
+}
+
+fn parse_target((n, text): Line) -> AocResult<usize> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
     match parts[..] {
-        ["  Starting items", item_list] => {
-            let item_list_parts: Vec<&str> = item_list.split(", ").collect();
-            for item in item_list_parts {
-                items.push(item.parse::<u64>().unwrap());
+        ["throw", "to", "monkey", m] => parse_number((n, m), "a monkey index"),
+        _ => parse_error_at(n, text.to_string(), "throw to monkey <number>".into()),
+    }
+}
+
+// Parses a single monkey definition, returning its index from the header, the
+// monkey and the line numbers of its throw targets (for later validation). The
+// attribute lines may appear in any order.
+fn parse_monkey(block: &[Line]) -> AocResult<(usize, Monkey, [usize; 2])> {
+    let (header_line, header) = block[0];
+    let index = match header.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["Monkey", index] if index.ends_with(':') => {
+            parse_number((header_line, &index[..index.len() - 1]), "a monkey index")?
+        }
+        _ => return parse_error_at(header_line, header.to_string(), "Monkey <number>:".into()),
+    };
+
+    let mut items = None;
+    let mut operation = None;
+    let mut divisor = None;
+    let mut monkey_true = None;
+    let mut monkey_false = None;
+    for &(n, line) in block[1..].iter() {
+        let (label, value) = match line.split_once(':') {
+            Some((label, value)) => (label.trim(), (n, value.trim())),
+            None => return parse_error_at(n, line.to_string(), "<attribute>: <value>".into()),
+        };
+        let duplicate = match label {
+            "Starting items" => items.replace(parse_items(value)?).is_some(),
+            "Operation" => operation.replace(parse_operation(value)?).is_some(),
+            "Test" => divisor.replace(parse_divisor(value)?).is_some(),
+            "If true" => monkey_true.replace((n, parse_target(value)?)).is_some(),
+            "If false" => monkey_false.replace((n, parse_target(value)?)).is_some(),
+            _ => {
+                let expected = "Starting items, Operation, Test, If true or If false";
+                return parse_error_at(n, line.to_string(), expected.into());
             }
+        };
+        if duplicate {
+            return parse_error_at(n, line.to_string(), format!("a single {label} line"));
         }
-        _ => panic!(),
     }
-    items
+
+    let missing = |attribute: &str| AocError::ParseErrorAt {
+        line: header_line,
+        got: header.to_string(),
+        expected: format!("a {attribute} line"),
+    };
+    let (true_line, monkey_true) = monkey_true.ok_or_else(|| missing("If true"))?;
+    let (false_line, monkey_false) = monkey_false.ok_or_else(|| missing("If false"))?;
+    let monkey = Monkey {
+        items: items.ok_or_else(|| missing("Starting items"))?,
+        operation: operation.ok_or_else(|| missing("Operation"))?,
+        divisor: divisor.ok_or_else(|| missing("Test"))?,
+        monkey_true,
+        monkey_false,
+    };
+    Ok((index, monkey, [true_line, false_line]))
 }
 
-fn parse_operation(line: &str) -> Operation {
-    let parts: Vec<&str> = line.split(": new = ").collect();
-    match parts[..] {
-        ["  Operation", operation_definition] => {
-            let operation_parts: Vec<&str> = operation_definition.split(" ").collect();
-            let left = match operation_parts[0] {
-                "old" => Value::Old,
-                n => Value::Const(n.parse().unwrap()),
-            };
-            let right = match operation_parts[2] {
-                "old" => Value::Old,
-                n => Value::Const(n.parse().unwrap()),
-            };
-            match operation_parts[1] {
-                "+" => Operation::Add(left, right),
-                "*" => Operation::Mul(left, right),
-                _ => panic!(),
+fn parse_monkeys(input: &[String]) -> AocResult<Vec<Monkey>> {
+    // split the input into blocks of non-blank lines
+    let mut blocks: Vec<Vec<Line>> = Vec::new();
+    let mut block = Vec::new();
+    for (i, line) in input.iter().enumerate() {
+        let line = line.trim();
+        if !line.is_empty() {
+            block.push((i + 1, line));
+        } else if !block.is_empty() {
+            blocks.push(block);
+            block = Vec::new();
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    // place each monkey at the index given by its header
+    let mut slots = vec![None; blocks.len()];
+    for block in blocks.iter() {
+        let (header_line, header) = block[0];
+        let (index, monkey, target_lines) = parse_monkey(block)?;
+        let expected = format!("a unique monkey index below {}", blocks.len());
+        match slots.get_mut(index) {
+            Some(slot @ None) => *slot = Some((monkey, target_lines)),
+            _ => return parse_error_at(header_line, header.to_string(), expected),
+        }
+    }
+
+    // all throws must go to another existing monkey
+    let n_monkeys = slots.len();
+    let mut monkeys = Vec::with_capacity(n_monkeys);
+    for (i, slot) in slots.into_iter().enumerate() {
+        let (monkey, target_lines) = slot.lift()?;
+        let targets = [monkey.monkey_true, monkey.monkey_false];
+        for (target, n) in targets.into_iter().zip(target_lines) {
+            if target >= n_monkeys || target == i {
+                let got = format!("throw to monkey {target}");
+                let expected = format!("a monkey index below {n_monkeys} other than {i}");
+                return parse_error_at(n, got, expected);
             }
         }
-        _ => panic!(),
+        monkeys.push(monkey);
     }
+    Ok(monkeys)
 }
 
-fn parse_divisor(line: &str) -> u64 {
-    let parts: Vec<&str> = line.split(": divisible by ").collect();
-    match parts[..] {
-        ["  Test", n] => n.parse().unwrap(),
-        _ => panic!(),
+#[derive(Debug, Clone, Copy)]
+enum WorryPolicy {
+    // worry levels are divided by 3 after each inspection
+    Relief,
+    // worry levels are kept modulo the given value (the lcm of all divisors)
+    Modular(u64),
+}
+
+impl WorryPolicy {
+    fn apply(&self, worry_level: u64) -> u64 {
+        match *self {
+            WorryPolicy::Relief => worry_level / 3,
+            WorryPolicy::Modular(m) => worry_level % m,
+        }
     }
 }
 
-fn parse_monkey_true(line: &str) -> usize {
-    let parts: Vec<&str> = line.split(": throw to monkey ").collect();
-    match parts[..] {
-        ["    If true", n] => n.parse().unwrap(),
-        _ => panic!(),
+// A single inspection: the inspecting monkey, the outcome of its test and
+// the worry level of the item as it gets thrown.
+#[derive(Debug, Clone, Copy)]
+struct Inspection {
+    monkey: usize,
+    passed: bool,
+    worry_level: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct MonkeyStats {
+    inspections: u128,
+    items_received: u128,
+    thrown_true: u128,
+    thrown_false: u128,
+    // the highest worry level of any item thrown by this monkey
+    max_worry_level: u64,
+}
+
+impl MonkeyStats {
+    fn merge(&mut self, other: &MonkeyStats) {
+        self.inspections += other.inspections;
+        self.items_received += other.items_received;
+        self.thrown_true += other.thrown_true;
+        self.thrown_false += other.thrown_false;
+        self.max_worry_level = self.max_worry_level.max(other.max_worry_level);
     }
 }
 
-fn parse_monkey_false(line: &str) -> usize {
-    let parts: Vec<&str> = line.split(": throw to monkey ").collect();
-    match parts[..] {
-        ["    If false", n] => n.parse().unwrap(),
-        _ => panic!(),
+// Adds an inspection, repeated `times` times, to the statistics.
+fn record(monkeys: &[Monkey], stats: &mut [MonkeyStats], inspection: &Inspection, times: u128) {
+    if times == 0 {
+        return;
     }
+    let monkey = &monkeys[inspection.monkey];
+    let thrower = &mut stats[inspection.monkey];
+    thrower.inspections += times;
+    thrower.max_worry_level = thrower.max_worry_level.max(inspection.worry_level);
+    let target = if inspection.passed {
+        thrower.thrown_true += times;
+        monkey.monkey_true
+    } else {
+        thrower.thrown_false += times;
+        monkey.monkey_false
+    };
+    stats[target].items_received += times;
 }
 
-fn parse_monkeys(input: &Vec<String>) -> Vec<Monkey> {
-    let monkey_lines: Vec<&str> = input
-        .iter()
-        .map(|line| line.as_str())
-        .filter(|line| *line != "")
-        .collect();
+// The inspections made by a single item over consecutive rounds, with the
+// inspections of round r stored in inspections[round_starts[r]..round_starts[r + 1]].
+struct Trace {
+    inspections: Vec<Inspection>,
+    round_starts: Vec<usize>,
+}
 
-    monkey_lines
-        .chunks(6)
-        .fold(Vec::new(), |mut monkeys, lines| {
-            let items = parse_items(lines[1]);
-            let operation = parse_operation(lines[2]);
-            let divisor = parse_divisor(lines[3]);
-            let monkey_true = parse_monkey_true(lines[4]);
-            let monkey_false = parse_monkey_false(lines[5]);
-            monkeys.push(Monkey {
-                items,
-                operation,
-                divisor,
-                monkey_false,
-                monkey_true,
-            });
-            monkeys
-        })
+impl Trace {
+    fn round(&self, r: usize) -> &[Inspection] {
+        &self.inspections[self.round_starts[r]..self.round_starts[r + 1]]
+    }
+
+    fn rounds(&self) -> usize {
+        self.round_starts.len() - 1
+    }
 }
 
-fn simulate_rounds(mut monkeys: Vec<Monkey>, n: u64, new_worry_level: impl Fn(u64) -> u64) -> u64 {
-    let mut inspection_counts = vec![0; monkeys.len()];
-    for _ in 0..n {
-        let mut i = 0;
-        while i < monkeys.len() {
-            inspection_counts[i] += monkeys[i].items.len() as u64;
-            while let Some(item) = monkeys[i].items.pop() {
-                let worry_level = new_worry_level(monkeys[i].operation.eval(item));
-                if worry_level % monkeys[i].divisor == 0 {
-                    let next = monkeys[i].monkey_true;
-                    monkeys[next].items.push(worry_level);
-                } else {
-                    let next = monkeys[i].monkey_false;
-                    monkeys[next].items.push(worry_level);
+// Moves a single item held by monkey `holder` through one round. Since items
+// never interact, this is all we need to know about an item to simulate it.
+fn item_round(
+    monkeys: &[Monkey],
+    policy: WorryPolicy,
+    mut holder: usize,
+    mut worry_level: u64,
+    inspections: &mut Vec<Inspection>,
+) -> (usize, u64) {
+    loop {
+        let monkey = &monkeys[holder];
+        let new_level = monkey.operation.eval(worry_level);
+        worry_level = policy.apply(new_level.expect("worry level overflowed a u64"));
+        let passed = worry_level.is_multiple_of(monkey.divisor);
+        inspections.push(Inspection {
+            monkey: holder,
+            passed,
+            worry_level,
+        });
+        let next = if passed {
+            monkey.monkey_true
+        } else {
+            monkey.monkey_false
+        };
+        // monkeys later in the order still get their turn this round
+        if next <= holder {
+            return (next, worry_level);
+        }
+        holder = next;
+    }
+}
+
+// Adds the inspections made by one item over n rounds to `stats`. Each
+// (monkey, worry level) state is recorded at the start of a round, so once a
+// state repeats the remaining rounds are whole repetitions of the cycle.
+fn simulate_item(
+    monkeys: &[Monkey],
+    n: u128,
+    policy: WorryPolicy,
+    mut state: (usize, u64),
+    stats: &mut [MonkeyStats],
+) {
+    let mut seen = HashMap::<(usize, u64), usize>::new();
+    let mut trace = Trace {
+        inspections: Vec::new(),
+        round_starts: vec![0],
+    };
+    while (trace.rounds() as u128) < n {
+        if let Some(&cycle_start) = seen.get(&state) {
+            let cycle_len = trace.rounds() - cycle_start;
+            let remaining = n - cycle_start as u128;
+            let repeats = remaining / cycle_len as u128;
+            let tail = (remaining % cycle_len as u128) as usize;
+            for r in 0..cycle_start {
+                for inspection in trace.round(r) {
+                    record(monkeys, stats, inspection, 1);
                 }
             }
-            i += 1;
+            for r in cycle_start..trace.rounds() {
+                let extra = if r - cycle_start < tail { 1 } else { 0 };
+                for inspection in trace.round(r) {
+                    record(monkeys, stats, inspection, repeats + extra);
+                }
+            }
+            return;
+        }
+        seen.insert(state, trace.rounds());
+        state = item_round(monkeys, policy, state.0, state.1, &mut trace.inspections);
+        trace.round_starts.push(trace.inspections.len());
+    }
+    for inspection in trace.inspections.iter() {
+        record(monkeys, stats, inspection, 1);
+    }
+}
+
+// Returns the statistics of each monkey after n rounds.
+fn simulate_rounds(monkeys: &[Monkey], n: u128, policy: WorryPolicy) -> Vec<MonkeyStats> {
+    let mut stats = vec![MonkeyStats::default(); monkeys.len()];
+    for (i, monkey) in monkeys.iter().enumerate() {
+        for &item in monkey.items.iter() {
+            simulate_item(monkeys, n, policy, (i, item), &mut stats);
         }
     }
+    stats
+}
+
+// Same as `simulate_rounds`, but with the items split across `threads`
+// threads. Items never interact, so each thread simulates its share of items
+// independently and the statistics are merged at the end.
+fn simulate_rounds_parallel(
+    monkeys: &[Monkey],
+    n: u128,
+    policy: WorryPolicy,
+    threads: usize,
+) -> Vec<MonkeyStats> {
+    let items: Vec<(usize, u64)> = monkeys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, monkey)| monkey.items.iter().map(move |&item| (i, item)))
+        .collect();
+    let chunk_size = items.len().div_ceil(threads.max(1)).max(1);
+    let mut stats = vec![MonkeyStats::default(); monkeys.len()];
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut stats = vec![MonkeyStats::default(); monkeys.len()];
+                    for &state in chunk {
+                        simulate_item(monkeys, n, policy, state, &mut stats);
+                    }
+                    stats
+                })
+            })
+            .collect();
+        for handle in handles {
+            let chunk_stats = handle.join().expect("simulation thread panicked");
+            for (total, s) in stats.iter_mut().zip(chunk_stats.iter()) {
+                total.merge(s);
+            }
+        }
+    });
+    stats
+}
+
+// The product of the inspection counts of the k most active monkeys.
+fn monkey_business(stats: &[MonkeyStats], k: usize) -> u128 {
+    let mut inspection_counts: Vec<u128> = stats.iter().map(|s| s.inspections).collect();
     inspection_counts.sort_by(|a, b| b.cmp(a));
-    inspection_counts[0] * inspection_counts[1]
+    inspection_counts.iter().take(k).product()
+}
+
+fn part1(monkeys: &[Monkey]) -> Vec<MonkeyStats> {
+    simulate_rounds(monkeys, 20, WorryPolicy::Relief)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
-fn part1(monkeys: Vec<Monkey>) -> u64 {
-    simulate_rounds(monkeys, 20, |n| n / 3)
+fn modular_policy(monkeys: &[Monkey]) -> WorryPolicy {
+    let lcm = monkeys.iter().fold(1, |lcm, m| {
+        let factor = m.divisor / gcd(lcm, m.divisor);
+        lcm.checked_mul(factor)
+            .expect("lcm of divisors overflowed a u64")
+    });
+    WorryPolicy::Modular(lcm)
 }
 
-fn part2(monkeys: Vec<Monkey>) -> u64 {
-    let max_worry_level = monkeys.iter().fold(1, |acc, m| acc * m.divisor);
-    simulate_rounds(monkeys, 10000, |n| n % max_worry_level)
+fn part2(monkeys: &[Monkey]) -> Vec<MonkeyStats> {
+    simulate_rounds(monkeys, 10000, modular_policy(monkeys))
 }
 
+// Generates a random (but valid) set of monkeys using a simple LCG. Divisors
+// are drawn from the primes below 25, so however many monkeys there are the
+// lcm (and so every worry level) stays below 2^28 and `old * old` never
+// overflows.
+fn generate_monkeys(n_monkeys: usize, items_per_monkey: usize, seed: u64) -> Vec<Monkey> {
+    const PRIMES: [u64; 9] = [2, 3, 5, 7, 11, 13, 17, 19, 23];
+    let mut rng = Lcg::new(seed);
+    assert!(n_monkeys > 1, "monkeys need someone to throw to");
+    (0..n_monkeys)
+        .map(|i| {
+            // any monkey other than i
+            let other_monkey =
+                |rng: &mut Lcg| (i + 1 + rng.next(n_monkeys as u64 - 1) as usize) % n_monkeys;
+            let items = (0..items_per_monkey).map(|_| 1 + rng.next(100)).collect();
+            let operation = match rng.next(3) {
+                0 => Operation::Add(Value::Old, Value::Const(1 + rng.next(9))),
+                1 => Operation::Mul(Value::Old, Value::Const(2 + rng.next(18))),
+                _ => Operation::Mul(Value::Old, Value::Old),
+            };
+            Monkey {
+                items,
+                operation,
+                divisor: PRIMES[i % PRIMES.len()],
+                monkey_true: other_monkey(&mut rng),
+                monkey_false: other_monkey(&mut rng),
+            }
+        })
+        .collect()
+}
+
+// Times the sequential and parallel simulations on a generated set of
+// monkeys, checking that both produce the same statistics.
+fn bench(n_monkeys: usize, items_per_monkey: usize, rounds: u128) {
+    let monkeys = generate_monkeys(n_monkeys, items_per_monkey, 2022);
+    let policy = modular_policy(&monkeys);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let start = Instant::now();
+    let sequential = simulate_rounds(&monkeys, rounds, policy);
+    println!("sequential: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let parallel = simulate_rounds_parallel(&monkeys, rounds, policy, threads);
+    println!("parallel ({threads} threads): {:?}", start.elapsed());
+
+    assert_eq!(sequential, parallel, "parallel simulation diverged");
+    println!("monkey business = {:?}", monkey_business(&parallel, 2));
+}
+
+fn print_stats(stats: &[MonkeyStats]) {
+    println!("monkey  inspections  received  thrown (true/false)  max worry");
+    for (i, s) in stats.iter().enumerate() {
+        println!(
+            "{:>6}  {:>11}  {:>8}  {:>19}  {:>9}",
+            i,
+            s.inspections,
+            s.items_received,
+            format!("{}/{}", s.thrown_true, s.thrown_false),
+            s.max_worry_level
+        );
+    }
+}
+
+// Usage: day11 [--top <k>] [--stats]
+//        day11 --bench <monkeys> <items per monkey> <rounds>
 fn main() {
+    let mut top = 2;
+    let mut show_stats = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => top = args.next().and_then(|k| k.parse().ok()).expect("--top <k>"),
+            "--stats" => show_stats = true,
+            "--bench" => {
+                let usage = "--bench <monkeys> <items per monkey> <rounds>";
+                let mut arg = || args.next().expect(usage);
+                let n_monkeys = arg().parse().expect(usage);
+                let items_per_monkey = arg().parse().expect(usage);
+                let rounds = arg().parse().expect(usage);
+                return bench(n_monkeys, items_per_monkey, rounds);
+            }
+            _ => panic!("unknown argument {arg}, expected --top <k>, --stats or --bench"),
+        }
+    }
+
     let input: Vec<String> = read_input();
-    let monkeys = parse_monkeys(&input);
-    println!("part 1 = {:?}", part1(monkeys.clone()));
-    println!("part 2 = {:?}", part2(monkeys));
+    let monkeys = parse_monkeys(&input).unwrap();
+    for (part, stats) in [(1, part1(&monkeys)), (2, part2(&monkeys))] {
+        println!("part {part} = {:?}", monkey_business(&stats, top));
+        if show_stats {
+            print_stats(&stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    fn example() -> Vec<Monkey> {
+        let input: Vec<String> = EXAMPLE.lines().map(String::from).collect();
+        parse_monkeys(&input).unwrap()
+    }
+
+    // The puzzle's own simulation: every monkey takes its turn every round,
+    // throwing all of its items one at a time.
+    fn simulate_plainly(monkeys: &[Monkey], n: u128, policy: WorryPolicy) -> Vec<MonkeyStats> {
+        let mut held: Vec<Vec<u64>> = monkeys.iter().map(|m| m.items.clone()).collect();
+        let mut stats = vec![MonkeyStats::default(); monkeys.len()];
+        for _ in 0..n {
+            for (i, monkey) in monkeys.iter().enumerate() {
+                for worry_level in std::mem::take(&mut held[i]) {
+                    let worry_level = policy.apply(monkey.operation.eval(worry_level).unwrap());
+                    let passed = worry_level.is_multiple_of(monkey.divisor);
+                    let inspection = Inspection {
+                        monkey: i,
+                        passed,
+                        worry_level,
+                    };
+                    record(monkeys, &mut stats, &inspection, 1);
+                    let target = if passed {
+                        monkey.monkey_true
+                    } else {
+                        monkey.monkey_false
+                    };
+                    held[target].push(worry_level);
+                }
+            }
+        }
+        stats
+    }
+
+    // On the example the items settle into cycles of 7 and 11 rounds with
+    // relief and of up to 448 rounds (starting as late as round 175) without,
+    // so these round counts cover stopping before, inside and past the first
+    // repetition of each cycle.
+    #[test]
+    fn cycle_skipping_matches_plain_simulation() {
+        let monkeys = example();
+        for n in [0, 1, 2, 3, 7, 8, 12, 20] {
+            assert_eq!(
+                simulate_rounds(&monkeys, n, WorryPolicy::Relief),
+                simulate_plainly(&monkeys, n, WorryPolicy::Relief),
+                "{n} rounds with relief"
+            );
+        }
+        let policy = modular_policy(&monkeys);
+        for n in [0, 1, 2, 20, 100, 175, 176, 200, 622, 623, 624, 1000, 10000] {
+            assert_eq!(
+                simulate_rounds(&monkeys, n, policy),
+                simulate_plainly(&monkeys, n, policy),
+                "{n} rounds kept modulo the divisors"
+            );
+        }
+    }
+
+    #[test]
+    fn monkey_business_matches_puzzle_example() {
+        let monkeys = example();
+        assert_eq!(monkey_business(&part1(&monkeys), 2), 10605);
+        assert_eq!(monkey_business(&part2(&monkeys), 2), 2713310158);
+    }
 }
@@ -1,106 +1,150 @@
 use aoc::io::*;
 use aoc::result::*;
-use std::collections::VecDeque;
+use std::fmt;
 
 /*
         [D]
-    [N] [C]      =>  [['N', 'Z'], ['D', 'C', 'M'], ['P']]
+    [N] [C]      =>  [['Z', 'N'], ['M', 'C', 'D'], ['P']]
     [Z] [M] [P]
      1   2   3
 */
-type Crates = Vec<VecDeque<char>>;
+// Each stack is listed bottom to top, so the top crate is the last one.
+type Crates = Vec<Vec<char>>;
 
-fn parse_crates(input: &Vec<&String>) -> AocResult<Crates> {
+fn parse_crates(input: &[&String]) -> AocResult<Crates> {
     let line_len = input[0].len();
     let n_stacks = (line_len + 1) / 4;
-    let mut crates = Vec::new();
-    for _ in 0..n_stacks {
-        crates.push(VecDeque::new());
-    }
+    let mut crates = vec![Vec::new(); n_stacks];
     for line in input {
         let mut chars = line.chars();
         match chars.nth(1) {
-            Some(c) if c.is_ascii_alphabetic() => crates[0].push_back(c),
+            Some(c) if c.is_ascii_alphabetic() => crates[0].push(c),
             Some(d) if d.is_ascii_digit() => break,
             _ => (), // pass,
         }
-        for i in 1..n_stacks {
+        for stack in crates.iter_mut().skip(1) {
             match chars.nth(3) {
-                Some(c) if c.is_ascii_alphabetic() => crates[i].push_back(c),
+                Some(c) if c.is_ascii_alphabetic() => stack.push(c),
                 _ => (), // pass,
             }
         }
     }
+    // the drawing is read top down
+    for stack in crates.iter_mut() {
+        stack.reverse();
+    }
     Ok(crates)
 }
 
 #[derive(Debug)]
 struct Move {
-    n: i32,
+    n: usize,
     src: usize,
     dest: usize,
 }
 
-fn parse_moves(input: &Vec<&String>) -> AocResult<Vec<Move>> {
-    input.iter().fold(Ok(Vec::new()), |ops, line| {
-        let mut ops = ops?;
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.n, self.src, self.dest)
+    }
+}
+
+fn parse_moves(input: &[&String]) -> AocResult<Vec<Move>> {
+    let mut moves = Vec::new();
+    for line in input {
         let lexemes: Vec<&str> = line.as_str().split(" ").collect();
-        match lexemes.as_slice() {
-            ["move", n, "from", src, "to", dest] => {
-                ops.push(Move {
-                    n: n.parse().lift()?,
-                    src: src.parse().lift()?,
-                    dest: dest.parse().lift()?,
-                });
-                Ok(ops)
-            }
+        let parsed = match lexemes.as_slice() {
+            ["move", n, "from", src, "to", dest] => (n.parse(), src.parse(), dest.parse()),
+            _ => return parse_error(line.to_string(), "move # from # to #".into()),
+        };
+        match parsed {
+            (Ok(n), Ok(src), Ok(dest)) => moves.push(Move { n, src, dest }),
             _ => return parse_error(line.to_string(), "move # from # to #".into()),
         }
-    })
+    }
+    Ok(moves)
 }
 
-fn parse_input(input: &Vec<String>) -> AocResult<(Crates, Vec<Move>)> {
-    let crate_input: Vec<&String> = input
-        .iter()
-        .take_while(|line| line.as_str() != "")
-        .collect();
+fn parse_input(input: &[String]) -> AocResult<(Crates, Vec<Move>)> {
+    let crate_input: Vec<&String> = input.iter().take_while(|line| !line.is_empty()).collect();
     let ops_input: Vec<&String> = input
         .iter()
-        .skip_while(|line| line.as_str() != "")
+        .skip_while(|line| !line.is_empty())
         .skip(1)
         .collect();
     Ok((parse_crates(&crate_input)?, parse_moves(&ops_input)?))
 }
 
 fn top_of_crates(crates: &Crates) -> String {
-    crates.iter().map(|c| c[0]).collect()
+    crates.iter().filter_map(|stack| stack.last()).collect()
 }
 
-fn part1(input: &Vec<String>) -> AocResult<String> {
-    let (mut crates, moves) = parse_input(input)?;
-    for Move { n, src, dest } in moves {
-        for _ in 0..n {
-            let c = crates[src - 1].pop_front().lift()?;
-            crates[dest - 1].push_front(c);
-        }
+// Takes the top n crates off the source stack, bottom to top, after checking
+// that the move fits the stacks.
+fn pick_up(crates: &mut Crates, m: &Move) -> AocResult<Vec<char>> {
+    let n_stacks = crates.len();
+    if !(1..=n_stacks).contains(&m.src) || !(1..=n_stacks).contains(&m.dest) {
+        return parse_error(m.to_string(), format!("stacks between 1 and {n_stacks}"));
     }
-    Ok(top_of_crates(&crates))
+    let stack = &mut crates[m.src - 1];
+    if stack.len() < m.n {
+        let expected = format!("at most {} crates from {}", stack.len(), m.src);
+        return parse_error(m.to_string(), expected);
+    }
+    Ok(stack.split_off(stack.len() - m.n))
 }
 
-fn part2(input: &Vec<String>) -> AocResult<String> {
-    let (mut crates, moves) = parse_input(input)?;
-    for Move { n, src, dest } in moves {
-        let mut cs = VecDeque::new();
-        for _ in 0..n {
-            cs.push_front(crates[src - 1].pop_front().lift()?);
-        }
-        for c in cs {
-            crates[dest - 1].push_front(c);
+// A crane rearranging the stacks one move at a time.
+trait Crane {
+    fn apply(&self, crates: &mut Crates, m: &Move) -> AocResult<()>;
+
+    fn run(&self, crates: &mut Crates, moves: &[Move]) -> AocResult<()> {
+        moves.iter().try_for_each(|m| self.apply(crates, m))
+    }
+}
+
+// Moves crates one at a time, which reverses the ones it moves to another
+// stack.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn apply(&self, crates: &mut Crates, m: &Move) -> AocResult<()> {
+        let lifted = pick_up(crates, m)?;
+        if m.src == m.dest {
+            // each crate lands back where it was picked up from
+            crates[m.dest - 1].extend(lifted);
+        } else {
+            crates[m.dest - 1].extend(lifted.into_iter().rev());
         }
+        Ok(())
+    }
+}
+
+// Moves crates all at once, keeping their order.
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn apply(&self, crates: &mut Crates, m: &Move) -> AocResult<()> {
+        let lifted = pick_up(crates, m)?;
+        crates[m.dest - 1].extend(lifted);
+        Ok(())
     }
+}
+
+fn operate(crane: &dyn Crane, input: &[String]) -> AocResult<String> {
+    let (mut crates, moves) = parse_input(input)?;
+    crane.run(&mut crates, &moves)?;
     Ok(top_of_crates(&crates))
 }
 
+fn part1(input: &[String]) -> AocResult<String> {
+    operate(&CrateMover9000, input)
+}
+
+fn part2(input: &[String]) -> AocResult<String> {
+    operate(&CrateMover9001, input)
+}
+
 fn main() {
     let input: Vec<String> = read_input();
     println!("part 1 = {:?}", part1(&input).unwrap());
@@ -1,84 +1,717 @@
 use aoc::io::*;
+use aoc::rand::Lcg;
 use aoc::result::*;
-use std::collections::VecDeque;
-
-#[derive(Debug, Clone)]
-struct DirSize<'a> {
-    dir: &'a str,
-    size: i32,
-}
-
-// Builds a vector of directory size for the given input.
-fn directoy_sizes(input: &Vec<String>) -> AocResult<Vec<DirSize>> {
-    // tracks the parent directory as we traverse
-    let mut dirs = VecDeque::<DirSize>::new();
-    // the current directory state
-    let mut dir = "/";
-    let mut size = 0;
-    // the final vector of sizes
-    let mut res = Vec::new();
-    for line in input.iter() {
+use std::collections::HashSet;
+
+#[derive(Debug)]
+enum NodeKind {
+    Dir { children: Vec<usize> },
+    File,
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    kind: NodeKind,
+    // A file's size, or the total size of everything in a directory.
+    size: u64,
+}
+
+// An in-memory filesystem. Nodes live in a single vector and refer to each
+// other by index, with the root directory at index 0. A node is always added
+// after its parent.
+#[derive(Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+const ROOT: usize = 0;
+
+impl FileSystem {
+    fn new() -> FileSystem {
+        let root = Node {
+            name: "/".into(),
+            parent: None,
+            kind: NodeKind::Dir {
+                children: Vec::new(),
+            },
+            size: 0,
+        };
+        FileSystem { nodes: vec![root] }
+    }
+
+    fn children(&self, node: usize) -> &[usize] {
+        match &self.nodes[node].kind {
+            NodeKind::Dir { children } => children,
+            NodeKind::File => &[],
+        }
+    }
+
+    fn is_dir(&self, node: usize) -> bool {
+        matches!(self.nodes[node].kind, NodeKind::Dir { .. })
+    }
+
+    fn child(&self, dir: usize, name: &str) -> Option<usize> {
+        let children = self.children(dir).iter();
+        children
+            .copied()
+            .find(|&child| self.nodes[child].name == name)
+    }
+
+    fn add(&mut self, parent: usize, name: &str, kind: NodeKind) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.into(),
+            parent: Some(parent),
+            kind,
+            size: 0,
+        });
+        if let NodeKind::Dir { children } = &mut self.nodes[parent].kind {
+            children.push(node);
+        }
+        node
+    }
+
+    // Returns the named subdirectory of dir, creating it if needed.
+    fn add_dir(&mut self, dir: usize, name: &str) -> usize {
+        match self.child(dir, name) {
+            Some(child) => child,
+            None => self.add(
+                dir,
+                name,
+                NodeKind::Dir {
+                    children: Vec::new(),
+                },
+            ),
+        }
+    }
+
+    // Adds a file and counts it towards every directory above it, or returns
+    // None if the totals would overflow. The root holds the largest total, so
+    // it is the only one that needs checking.
+    fn add_file(&mut self, dir: usize, name: &str, size: u64) -> Option<usize> {
+        self.nodes[ROOT].size.checked_add(size)?;
+        let file = self.add(dir, name, NodeKind::File);
+        self.nodes[file].size = size;
+        let mut ancestor = Some(dir);
+        while let Some(node) = ancestor {
+            self.nodes[node].size += size;
+            ancestor = self.nodes[node].parent;
+        }
+        Some(file)
+    }
+
+    // The full path of a node, e.g. `/a/e`.
+    fn path(&self, node: usize) -> String {
+        match self.nodes[node].parent {
+            None => "/".into(),
+            Some(ROOT) => format!("/{}", self.nodes[node].name),
+            Some(parent) => format!("{}/{}", self.path(parent), self.nodes[node].name),
+        }
+    }
+
+    // Finds a node by its full path.
+    fn lookup(&self, path: &str) -> Option<usize> {
+        let mut names = path.split('/').filter(|name| !name.is_empty());
+        names.try_fold(ROOT, |node, name| self.child(node, name))
+    }
+
+    // The total size of every node, indexed like the nodes.
+    fn sizes(&self) -> Vec<u64> {
+        self.nodes.iter().map(|node| node.size).collect()
+    }
+
+    fn dirs(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&node| self.is_dir(node))
+    }
+
+    // The full path and total size of every directory.
+    fn dir_sizes(&self) -> Vec<(String, u64)> {
+        let sizes = self.sizes();
+        self.dirs()
+            .map(|dir| (self.path(dir), sizes[dir]))
+            .collect()
+    }
+}
+
+// Replays a transcript of `cd` and `ls` commands. Directories are remembered
+// once listed, so a repeated `ls` must agree with the first one instead of
+// adding its entries again.
+struct Transcript {
+    fs: FileSystem,
+    cwd: usize,
+    listed: HashSet<usize>,
+    // The directory being listed, and whether it had been listed before.
+    listing: Option<(usize, bool)>,
+}
+
+impl Transcript {
+    // Follows a `cd` target, which may be absolute and may have several
+    // components. Unknown directories are created unless their parent has
+    // already been listed without them.
+    fn cd(&mut self, target: &str) -> Result<(), String> {
+        let mut dir = if target.starts_with('/') {
+            ROOT
+        } else {
+            self.cwd
+        };
+        for name in target.split('/').filter(|name| !name.is_empty()) {
+            dir = match name {
+                "." => dir,
+                ".." => match self.fs.nodes[dir].parent {
+                    Some(parent) => parent,
+                    None => return Err("a directory below /".into()),
+                },
+                _ => match self.fs.child(dir, name) {
+                    Some(child) if self.fs.is_dir(child) => child,
+                    Some(_) => return Err(format!("a directory, but {name} is a file")),
+                    None if self.listed.contains(&dir) => {
+                        let path = self.fs.path(dir);
+                        return Err(format!("a directory listed in {path}"));
+                    }
+                    None => self.fs.add_dir(dir, name),
+                },
+            }
+        }
+        self.cwd = dir;
+        Ok(())
+    }
+
+    fn ls(&mut self) {
+        let relisting = !self.listed.insert(self.cwd);
+        self.listing = Some((self.cwd, relisting));
+    }
+
+    // Records one line of `ls` output, where size is None for a directory.
+    fn entry(&mut self, name: &str, size: Option<u64>) -> Result<(), String> {
+        let Some((dir, relisting)) = self.listing else {
+            return Err("a command".into());
+        };
+        match (self.fs.child(dir, name), size) {
+            (Some(child), None) if self.fs.is_dir(child) => Ok(()),
+            (Some(child), Some(_)) if self.fs.is_dir(child) => {
+                Err(format!("dir {name}, as listed before"))
+            }
+            (Some(child), Some(size)) => match self.fs.nodes[child].size {
+                earlier if earlier == size => Ok(()),
+                earlier => Err(format!("{name} with its earlier size {earlier}")),
+            },
+            (Some(_), None) => Err(format!("{name} as the file listed before")),
+            (None, _) if relisting => {
+                let path = self.fs.path(dir);
+                Err(format!("only entries from the earlier listing of {path}"))
+            }
+            (None, None) => {
+                self.fs.add_dir(dir, name);
+                Ok(())
+            }
+            (None, Some(size)) => match self.fs.add_file(dir, name, size) {
+                Some(_) => Ok(()),
+                None => Err(format!("sizes totalling at most {}", u64::MAX)),
+            },
+        }
+    }
+}
+
+// Rebuilds the filesystem from a transcript, reporting commands and listings
+// that contradict what came before.
+fn parse_transcript(input: &[String]) -> AocResult<FileSystem> {
+    let mut transcript = Transcript {
+        fs: FileSystem::new(),
+        cwd: ROOT,
+        listed: HashSet::new(),
+        listing: None,
+    };
+    for (i, line) in input.iter().enumerate() {
         let parts: Vec<&str> = line.as_str().split(" ").collect();
-        match parts[..] {
-            // noop
-            ["$", "cd", "/"] => (),
-            // add the current directory to res and restore the parent
-            // directory state, while combing the child directory size
-            ["$", "cd", ".."] => {
-                res.push(DirSize { dir, size });
-                let parent = dirs.pop_front().lift()?;
-                size += parent.size;
-                dir = parent.dir;
+        let result = match parts[..] {
+            ["$", "cd", target] => {
+                transcript.listing = None;
+                transcript.cd(target)
             }
-            // add the current directory state to the stack, and clear the
-            // directory state
-            ["$", "cd", child] => {
-                dirs.push_front(DirSize { dir, size });
-                size = 0;
-                dir = child;
+            ["$", "ls"] => {
+                transcript.ls();
+                Ok(())
             }
-            // noops
-            ["$", "ls"] => (),
-            ["dir", _] => (),
-            // add the files size to the current directory size
-            [file_size, _] => size += file_size.parse::<i32>().lift()?,
+            ["$", ..] => Err("cd <dir> or ls".into()),
+            ["dir", name] => transcript.entry(name, None),
+            [file_size, name] => match file_size.parse() {
+                Ok(size) => transcript.entry(name, Some(size)),
+                Err(_) => Err("<size> <name> or dir <name>".into()),
+            },
             // bad input
-            _ => return parse_error(line.to_string(), "a command or output".into()),
+            _ => Err("a command or output".into()),
+        };
+        if let Err(expected) = result {
+            return parse_error_at(i + 1, line.to_string(), expected);
+        }
+    }
+    Ok(transcript.fs)
+}
+
+// Formats a size the way `du -h` does, with binary units and one decimal
+// below 10.
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 7] = ["", "K", "M", "G", "T", "P", "E"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size}")
+    } else if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+fn format_size(size: u64, human: bool) -> String {
+    if human {
+        human_size(size)
+    } else {
+        size.to_string()
+    }
+}
+
+// Draws the filesystem like `tree`, with each node's total size.
+fn render_tree(fs: &FileSystem, human: bool) -> String {
+    fn walk(
+        fs: &FileSystem,
+        sizes: &[u64],
+        human: bool,
+        node: usize,
+        prefix: &str,
+        out: &mut String,
+    ) {
+        let children = fs.children(node);
+        for (i, &child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let suffix = if fs.is_dir(child) { "/" } else { "" };
+            let size = format_size(sizes[child], human);
+            let name = &fs.nodes[child].name;
+            out.push_str(&format!("{prefix}{branch}{name}{suffix} ({size})\n"));
+            walk(fs, sizes, human, child, &format!("{prefix}{indent}"), out);
+        }
+    }
+
+    let sizes = fs.sizes();
+    let mut out = format!("/ ({})\n", format_size(sizes[ROOT], human));
+    walk(fs, &sizes, human, ROOT, "", &mut out);
+    out
+}
+
+// Every directory, largest first.
+fn largest_dirs(fs: &FileSystem) -> Vec<(String, u64)> {
+    let mut dirs = fs.dir_sizes();
+    dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    dirs
+}
+
+// Lists directories like `du | sort -rn`, largest first.
+fn render_du(dirs: &[(String, u64)], human: bool) -> String {
+    let lines = dirs
+        .iter()
+        .map(|(path, size)| format!("{}\t{}\n", format_size(*size, human), path));
+    lines.collect()
+}
+
+// The size of a disk and the free space it must end up with.
+struct Disk {
+    capacity: u64,
+    required: u64,
+}
+
+impl Disk {
+    const PUZZLE: Disk = Disk {
+        capacity: 70_000_000,
+        required: 30_000_000,
+    };
+
+    // How many bytes must be deleted, which is zero when there is already
+    // enough free space.
+    fn needed(&self, used: u64) -> u64 {
+        used.saturating_add(self.required)
+            .saturating_sub(self.capacity)
+    }
+}
+
+// Directories to delete and the space that frees.
+#[derive(Debug, Default)]
+struct Plan {
+    dirs: Vec<usize>,
+    freed: u64,
+}
+
+// Plans deleting the single smallest directory that frees enough space.
+fn smallest_deletion(fs: &FileSystem, disk: &Disk) -> Option<Plan> {
+    let sizes = fs.sizes();
+    let needed = disk.needed(sizes[ROOT]);
+    if needed == 0 {
+        return Some(Plan::default());
+    }
+    let dir = fs
+        .dirs()
+        .filter(|&dir| sizes[dir] >= needed)
+        .min_by_key(|&dir| sizes[dir])?;
+    Some(Plan {
+        dirs: vec![dir],
+        freed: sizes[dir],
+    })
+}
+
+// The directories in the order `minimal_deletion` decides on them (each one
+// before everything inside it), with the state of its search.
+struct DeletionSearch {
+    dirs: Vec<usize>,
+    sizes: Vec<u64>,
+    // the position just past everything inside each directory
+    skip: Vec<usize>,
+    // the most that deleting directories from each position on can free
+    rest: Vec<u64>,
+    needed: u64,
+    best: Plan,
+    picked: Vec<usize>,
+    // how many more positions the search may visit
+    budget: usize,
+}
+
+impl DeletionSearch {
+    // Either deletes the directory at position i and skips what's inside it,
+    // or keeps it and moves on to the first directory inside it.
+    fn search(&mut self, i: usize, freed: u64) {
+        if freed >= self.needed {
+            if freed < self.best.freed {
+                self.best = Plan {
+                    dirs: self.picked.clone(),
+                    freed,
+                };
+            }
+            return;
+        }
+        if i == self.dirs.len() || freed + self.rest[i] < self.needed || self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+        if freed + self.sizes[i] < self.best.freed {
+            self.picked.push(self.dirs[i]);
+            self.search(self.skip[i], freed + self.sizes[i]);
+            self.picked.pop();
+        }
+        // nothing frees less than exactly what's needed
+        if self.best.freed > self.needed {
+            self.search(i + 1, freed);
         }
     }
-    // pop all the remaining parents off the stack up to "/"
-    while let Some(parent) = dirs.pop_front() {
-        res.push(DirSize { dir, size });
-        size += parent.size;
-        dir = parent.dir;
+}
+
+// Plans deleting a set of directories, none inside another, that frees enough
+// space while deleting as few bytes as possible.
+//
+// This is a branch and bound search, deciding on each directory in turn
+// whether to delete it. It starts from the single smallest deletion and
+// abandons any partial set that already frees more than the best found, or
+// can no longer free enough. Larger directories are tried first, so good sets
+// turn up early. Finding the best set is still exponential in the worst case,
+// so after MAX_STEPS this settles for the best set found, returning false
+// with it.
+fn minimal_deletion(fs: &FileSystem, disk: &Disk) -> Option<(Plan, bool)> {
+    const MAX_STEPS: usize = 50_000_000;
+    let best = smallest_deletion(fs, disk)?;
+    let sizes = fs.sizes();
+    let needed = disk.needed(sizes[ROOT]);
+    if needed == 0 {
+        return Some((best, true));
     }
-    // add "/"
-    res.push(DirSize { dir, size });
-    Ok(res)
+
+    // the directories before everything inside them, largest first
+    let mut dirs = Vec::new();
+    let mut skip = Vec::new();
+    // each directory is pushed again (with its position) to be closed once
+    // everything inside it is done
+    let mut pending = vec![(ROOT, None)];
+    while let Some((dir, opened_at)) = pending.pop() {
+        if let Some(i) = opened_at {
+            skip[i] = dirs.len();
+            continue;
+        }
+        pending.push((dir, Some(dirs.len())));
+        dirs.push(dir);
+        skip.push(0);
+        let children = fs.children(dir).iter().copied();
+        let mut subdirs: Vec<usize> = children.filter(|&child| fs.is_dir(child)).collect();
+        subdirs.sort_by_key(|&subdir| sizes[subdir]);
+        pending.extend(subdirs.into_iter().map(|subdir| (subdir, None)));
+    }
+    let mut rest = vec![0; dirs.len() + 1];
+    for i in (0..dirs.len()).rev() {
+        rest[i] = sizes[dirs[i]] + rest[skip[i]];
+    }
+
+    let mut search = DeletionSearch {
+        sizes: dirs.iter().map(|&dir| sizes[dir]).collect(),
+        dirs,
+        skip,
+        rest,
+        needed,
+        best,
+        picked: Vec::new(),
+        budget: MAX_STEPS,
+    };
+    search.search(0, 0);
+    let finished = search.budget > 0;
+    Some((search.best, finished))
 }
 
-fn part1(input: &Vec<String>) -> AocResult<i32> {
-    let mut dirs = directoy_sizes(input)?;
-    dirs.retain(|d| d.size <= 100_000);
-    Ok(dirs.iter().fold(0, |sum, dir| sum + dir.size))
+fn render_plan(fs: &FileSystem, plan: &Plan, human: bool) -> String {
+    let sizes = fs.sizes();
+    let mut out = format!("free {}:\n", format_size(plan.freed, human));
+    for &dir in plan.dirs.iter() {
+        let size = format_size(sizes[dir], human);
+        out.push_str(&format!("{}\t{}\n", size, fs.path(dir)));
+    }
+    out
 }
 
-fn part2(input: &Vec<String>) -> AocResult<i32> {
-    const MAX_SPACE: i32 = 70_000_000;
-    let mut dirs = directoy_sizes(input)?;
-    dirs.sort_by(|a, b| b.size.cmp(&a.size));
-    let used_space = dirs[0].size;
-    let free_space = MAX_SPACE - used_space;
-    for (i, dir) in dirs.iter().enumerate() {
-        if free_space + dir.size < 30_000_000 {
-            return Ok(dirs[i - 1].size);
+// Generates a transcript exploring a random filesystem depth first, for
+// testing with much larger trees than the puzzle's. File sizes are spread
+// up to max_size.
+fn generate_transcript(n_dirs: usize, n_files: usize, max_size: u64, seed: u64) -> Vec<String> {
+    let mut rng = Lcg::new(seed);
+    // directory 0 is the root, and every other directory goes under an
+    // earlier one
+    let mut subdirs = vec![Vec::new(); n_dirs + 1];
+    for dir in 1..=n_dirs {
+        subdirs[rng.next(dir as u64) as usize].push(dir);
+    }
+    let mut files = vec![Vec::new(); n_dirs + 1];
+    for file in 0..n_files {
+        let size = ((rng.next(1 << 31) as u128 * max_size as u128) >> 31) as u64;
+        files[rng.next(n_dirs as u64 + 1) as usize].push((file, size.max(1)));
+    }
+
+    fn explore(
+        dir: usize,
+        subdirs: &[Vec<usize>],
+        files: &[Vec<(usize, u64)>],
+        out: &mut Vec<String>,
+    ) {
+        out.push("$ ls".into());
+        out.extend(subdirs[dir].iter().map(|sub| format!("dir d{sub}")));
+        out.extend(
+            files[dir]
+                .iter()
+                .map(|(file, size)| format!("{size} f{file}")),
+        );
+        for sub in subdirs[dir].iter() {
+            out.push(format!("$ cd d{sub}"));
+            explore(*sub, subdirs, files, out);
+            out.push("$ cd ..".into());
         }
     }
-    Err("Not found").lift()
+
+    let mut out = vec!["$ cd /".to_string()];
+    explore(0, &subdirs, &files, &mut out);
+    out
+}
+
+fn part1(fs: &FileSystem) -> u64 {
+    let dirs = fs.dir_sizes();
+    dirs.iter()
+        .map(|(_, size)| *size)
+        .filter(|&size| size <= 100_000)
+        .sum()
 }
 
+fn part2(fs: &FileSystem) -> AocResult<u64> {
+    match smallest_deletion(fs, &Disk::PUZZLE) {
+        Some(plan) => Ok(plan.freed),
+        None => Err("Not found").lift(),
+    }
+}
+
+// Usage: day7 [--path <path>] [--tree] [--du] [--top <n>] [--human]
+//             [--disk <capacity> <required>] [--plan] [--plan-set]
+//        day7 --generate <dirs> <files> <max file size>
 fn main() {
+    let mut paths = Vec::new();
+    let mut tree = false;
+    let mut du = false;
+    let mut top = None;
+    let mut human = false;
+    let mut disk = Disk::PUZZLE;
+    let mut plan = false;
+    let mut plan_set = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--path" => paths.push(args.next().expect("--path <path>")),
+            "--tree" => tree = true,
+            "--du" => du = true,
+            "--top" => {
+                let n = args.next().and_then(|n| n.parse::<usize>().ok());
+                top = Some(n.expect("--top <n>"));
+            }
+            "--human" | "-h" => human = true,
+            "--disk" => {
+                let mut size = || args.next().and_then(|size| size.parse::<u64>().ok());
+                let (capacity, required) = (size(), size());
+                disk = Disk {
+                    capacity: capacity.expect("--disk <capacity> <required>"),
+                    required: required.expect("--disk <capacity> <required>"),
+                };
+            }
+            "--generate" => {
+                let usage = "--generate <dirs> <files> <max file size>";
+                let mut arg = || args.next().expect(usage);
+                let n_dirs = arg().parse().expect(usage);
+                let n_files = arg().parse().expect(usage);
+                let max_size = arg().parse().expect(usage);
+                for line in generate_transcript(n_dirs, n_files, max_size, 7) {
+                    println!("{line}");
+                }
+                return;
+            }
+            "--plan" => plan = true,
+            "--plan-set" => plan_set = true,
+            _ => panic!(
+                "unknown argument {arg}, expected --path <path>, --tree, --du, --top <n>, --human, \
+                 --disk <capacity> <required>, --plan, --plan-set or --generate <dirs> <files> <max file size>"
+            ),
+        }
+    }
+
     let input: Vec<String> = read_input();
-    println!("part 1 = {:?}", part1(&input).unwrap());
-    println!("part 2 = {:?}", part2(&input).unwrap());
+    let fs = parse_transcript(&input).unwrap();
+    let sizes = fs.sizes();
+    for path in paths {
+        match fs.lookup(&path) {
+            Some(node) => println!("{} = {}", fs.path(node), sizes[node]),
+            None => println!("{path} not found"),
+        }
+    }
+    if tree {
+        print!("{}", render_tree(&fs, human));
+    }
+    let dirs = largest_dirs(&fs);
+    if du {
+        print!("{}", render_du(&dirs, human));
+    }
+    if let Some(n) = top {
+        println!("top {n} directories:");
+        print!("{}", render_du(&dirs[..n.min(dirs.len())], human));
+    }
+    if plan {
+        match smallest_deletion(&fs, &disk) {
+            Some(plan) => print!("{}", render_plan(&fs, &plan, human)),
+            None => println!("no single directory frees enough space"),
+        }
+    }
+    if plan_set {
+        match minimal_deletion(&fs, &disk) {
+            Some((plan, finished)) => {
+                if !finished {
+                    println!("search cut short, this may not be the best set");
+                }
+                print!("{}", render_plan(&fs, &plan, human));
+            }
+            None => println!("no set of directories frees enough space"),
+        }
+    }
+    println!("part 1 = {:?}", part1(&fs));
+    println!("part 2 = {:?}", part2(&fs).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+    fn example() -> FileSystem {
+        let input: Vec<String> = EXAMPLE.lines().map(String::from).collect();
+        parse_transcript(&input).unwrap()
+    }
+
+    fn paths(fs: &FileSystem, plan: &Plan) -> Vec<String> {
+        plan.dirs.iter().map(|&dir| fs.path(dir)).collect()
+    }
+
+    #[test]
+    fn plans_match_on_the_puzzle_example() {
+        let fs = example();
+        let single = smallest_deletion(&fs, &Disk::PUZZLE).unwrap();
+        let (set, finished) = minimal_deletion(&fs, &Disk::PUZZLE).unwrap();
+        assert!(finished);
+        assert_eq!(single.freed, 24933642);
+        assert_eq!(set.freed, single.freed);
+        assert_eq!(paths(&fs, &set), ["/d"]);
+    }
+
+    #[test]
+    fn set_plan_beats_single_plan() {
+        let input = [
+            "$ cd /",
+            "$ ls",
+            "dir x",
+            "dir y",
+            "dir z",
+            "$ cd x",
+            "$ ls",
+            "3000000 f",
+            "dir w",
+            "$ cd w",
+            "$ ls",
+            "1600000 q",
+            "$ cd /y",
+            "$ ls",
+            "2000000 g",
+            "$ cd ../z",
+            "$ ls",
+            "10000000 h",
+        ]
+        .map(String::from);
+        let fs = parse_transcript(&input).unwrap();
+        // 16600000 used, so 5600000 bytes must go
+        let disk = Disk {
+            capacity: 20_000_000,
+            required: 9_000_000,
+        };
+        let single = smallest_deletion(&fs, &disk).unwrap();
+        let (set, finished) = minimal_deletion(&fs, &disk).unwrap();
+        assert!(finished);
+        assert_eq!(paths(&fs, &single), ["/z"]);
+        assert_eq!(set.freed, 6_600_000);
+        assert_eq!(paths(&fs, &set), ["/x", "/y"]);
+    }
 }
@@ -1,100 +1,502 @@
 use aoc::io::*;
-use std::cmp::min;
-
-fn part1(input: &Vec<String>) -> i32 {
-    let n = input.len();
-    let mut visible = vec![false; n * n];
-    let default_visible = 4 * (n as i32 - 1);
-    let mut total_visible = default_visible;
-    let mut count = |x, y| {
-        if visible[y * n + x] == false {
-            total_visible += 1;
-            visible[y * n + x] = true;
+use aoc::rand::Lcg;
+use aoc::result::*;
+use std::fs;
+use std::iter;
+use std::time::Instant;
+
+// A W×H grid of tree heights, stored row by row.
+#[derive(Debug)]
+struct Forest {
+    heights: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+// A direction to look in, as (dx, dy) with y growing downwards.
+type Direction = (isize, isize);
+
+#[derive(Debug, Clone, Copy)]
+enum DirectionSet {
+    Cardinal,
+    Diagonal,
+    All,
+}
+
+impl DirectionSet {
+    // The directions in the set, in the order used for visibility flags.
+    fn directions(&self) -> &'static [Direction] {
+        const ALL: [Direction; 8] = [
+            // left, right, up, down
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            // up-left, up-right, down-left, down-right
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ];
+        match self {
+            DirectionSet::Cardinal => &ALL[..4],
+            DirectionSet::Diagonal => &ALL[4..],
+            DirectionSet::All => &ALL,
         }
-    };
-    for i in 1..n - 1 {
-        // left to right, top to bottom
-        let row = input[i].as_bytes();
-        let mut max_a = row[0];
-        let mut max_b = input[0].as_bytes()[i];
-        for j in 1..n - 1 {
-            if row[j] > max_a {
-                max_a = row[j];
-                count(j, i);
-            }
-            let col = input[j].as_bytes();
-            if col[i] > max_b {
-                max_b = col[i];
-                count(i, j);
+    }
+}
+
+// The positions along a ray through the forest, from (but not including) a
+// starting position up to the edge.
+struct Ray<'a> {
+    forest: &'a Forest,
+    pos: (usize, usize),
+    dir: Direction,
+}
+
+impl Iterator for Ray<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pos = self.forest.step(self.pos, self.dir)?;
+        Some(self.pos)
+    }
+}
+
+impl Forest {
+    fn get(&self, (x, y): (usize, usize)) -> u8 {
+        self.heights[y * self.width + x]
+    }
+
+    fn step(&self, (x, y): (usize, usize), (dx, dy): Direction) -> Option<(usize, usize)> {
+        let x = x.checked_add_signed(dx).filter(|&x| x < self.width)?;
+        let y = y.checked_add_signed(dy).filter(|&y| y < self.height)?;
+        Some((x, y))
+    }
+
+    fn ray(&self, pos: (usize, usize), dir: Direction) -> Ray<'_> {
+        Ray {
+            forest: self,
+            pos,
+            dir,
+        }
+    }
+
+    // Every line of trees running against the given direction, each starting
+    // at the edge that direction looks out of. Looking back towards the
+    // start of a line is looking in the given direction.
+    fn lines(
+        &self,
+        (dx, dy): Direction,
+    ) -> impl Iterator<Item = impl Iterator<Item = (usize, usize)> + '_> + '_ {
+        let empty = self.width == 0 || self.height == 0;
+        let x_edge = if dx < 0 {
+            0
+        } else {
+            self.width.saturating_sub(1)
+        };
+        let y_edge = if dy < 0 {
+            0
+        } else {
+            self.height.saturating_sub(1)
+        };
+        // the edge column when looking sideways, and the edge row when looking
+        // up or down, without counting the corner they share twice
+        let column = (0..self.height)
+            .filter(move |_| !empty && dx != 0)
+            .map(move |y| (x_edge, y));
+        let row = (0..self.width)
+            .filter(move |&x| !empty && dy != 0 && (dx == 0 || x != x_edge))
+            .map(move |x| (x, y_edge));
+        column
+            .chain(row)
+            .map(move |start| iter::once(start).chain(self.ray(start, (-dx, -dy))))
+    }
+
+    // The number of trees visible from a position looking in the given
+    // direction, up to and including the first tree at least as tall.
+    fn viewing_distance(&self, pos: (usize, usize), dir: Direction) -> usize {
+        let tree_height = self.get(pos);
+        let mut distance = 0;
+        for other in self.ray(pos, dir) {
+            distance += 1;
+            if self.get(other) >= tree_height {
+                break;
             }
         }
-        // right to left, bottom to top
-        max_a = row[n - 1];
-        max_b = input[n - 1].as_bytes()[i];
-        for j in (1..n - 1).rev() {
-            if row[j] > max_a {
-                max_a = row[j];
-                count(j, i);
+        distance
+    }
+}
+
+fn parse_forest(input: &[String]) -> AocResult<Forest> {
+    let width = input.first().map_or(0, String::len);
+    let mut heights = Vec::with_capacity(width * input.len());
+    for (i, line) in input.iter().enumerate() {
+        if line.len() != width {
+            let expected = format!("a row of {width} trees");
+            return parse_error_at(i + 1, line.to_string(), expected);
+        }
+        for c in line.chars() {
+            match c.to_digit(10) {
+                Some(height) => heights.push(height as u8),
+                None => return parse_error_at(i + 1, line.to_string(), "digits".into()),
             }
-            let col = input[j].as_bytes();
-            if col[i] > max_b {
-                max_b = col[i];
-                count(i, j);
+        }
+    }
+    Ok(Forest {
+        heights,
+        width,
+        height: input.len(),
+    })
+}
+
+// For every tree, the directions it can be seen from outside the forest, as
+// bit flags in the order of the direction set.
+fn visibility(forest: &Forest, directions: DirectionSet) -> Vec<u8> {
+    let mut visible = vec![0; forest.width * forest.height];
+    for (bit, &dir) in directions.directions().iter().enumerate() {
+        // flag each tree taller than every tree between it and the edge
+        for line in forest.lines(dir) {
+            let mut max_height = None;
+            for (x, y) in line {
+                let tree_height = Some(forest.get((x, y)));
+                if tree_height > max_height {
+                    max_height = tree_height;
+                    visible[y * forest.width + x] |= 1 << bit;
+                }
             }
         }
     }
-    return total_visible;
+    visible
 }
 
-fn part2(input: &Vec<String>) -> usize {
-    let n = input.len();
-    let mut max_score = 0;
-    for y in 0..n {
-        let row = input[y].as_bytes();
-        for x in 0..n {
-            let tree_height = row[x];
-            let mut score = 1;
-            // up
-            let mut y2 = y;
-            if y > 0 {
-                y2 = y - 1;
-                while y2 > 0 && input[y2].as_bytes()[x] < tree_height {
-                    y2 -= 1;
+fn part1(forest: &Forest, directions: DirectionSet) -> usize {
+    let visible = visibility(forest, directions);
+    visible.iter().filter(|&&from| from != 0).count()
+}
+
+// The scenic score of every tree, walking outwards from each tree in every
+// direction. This is O(W·H·(W+H)), but easy to trust.
+fn scenic_scores_brute(forest: &Forest, directions: DirectionSet) -> Vec<u128> {
+    let mut scores = Vec::with_capacity(forest.width * forest.height);
+    for y in 0..forest.height {
+        for x in 0..forest.width {
+            let distances = directions.directions().iter();
+            scores.push(
+                distances
+                    .map(|&dir| forest.viewing_distance((x, y), dir) as u128)
+                    .product(),
+            );
+        }
+    }
+    scores
+}
+
+// Pops the trees hidden by a tree of the given height at position i along
+// a line, then returns its viewing distance back towards the start of the
+// line (the distance to the tree left on top of the stack, or to the edge).
+fn view_back(stack: &mut Vec<(usize, u8)>, i: usize, tree_height: u8) -> usize {
+    while stack.last().is_some_and(|&(_, h)| h < tree_height) {
+        stack.pop();
+    }
+    let distance = match stack.last() {
+        Some(&(j, _)) => i - j,
+        None => i,
+    };
+    stack.push((i, tree_height));
+    distance
+}
+
+// The scenic score of every tree in O(W·H) per direction. Each line of trees
+// is walked while keeping a stack of the trees that could still block the
+// view back along the line. A tree hides every shorter tree behind it, so
+// those get popped, and whatever is left on top blocks its view.
+//
+// Rather than following each line through the grid, the lines are walked
+// together a row at a time (with a stack per line), so that heights and
+// scores are read and written in order.
+//
+// Scores are u128s, as eight views of a few hundred trees each are enough to
+// overflow a u64.
+fn scenic_scores(forest: &Forest, directions: DirectionSet) -> Vec<u128> {
+    let Forest { width, height, .. } = *forest;
+    let mut scores = vec![1; width * height];
+    // each stack is paired with how far along its line the walk has got
+    let mut stacks: Vec<(Vec<(usize, u8)>, usize)> = vec![(Vec::new(), 0); width.max(1)];
+    for &(dx, dy) in directions.directions() {
+        if dy == 0 {
+            // the lines are the rows themselves
+            let stack = &mut stacks[0].0;
+            for y in 0..height {
+                stack.clear();
+                for i in 0..width {
+                    let x = if dx < 0 { i } else { width - 1 - i };
+                    let index = y * width + x;
+                    scores[index] *= view_back(stack, i, forest.heights[index]) as u128;
                 }
             }
-            score *= y - min(y2, n - 1);
-            // down
-            y2 = y + 1;
-            while y2 < n && input[y2].as_bytes()[x] < tree_height {
-                y2 += 1;
+            continue;
+        }
+        for i in 0..height {
+            let y = if dy < 0 { i } else { height - 1 - i };
+            // the line through (x, y) came through (x + dx, y + dy) in the
+            // previous row, and the line that ran off the side is replaced by
+            // one starting at the edge
+            match dx {
+                -1 => stacks.rotate_right(1),
+                1 => stacks.rotate_left(1),
+                _ => (),
             }
-            score *= min(y2, n - 1) - y;
-            // left
-            let mut x2 = x;
-            if x > 0 {
-                x2 = x - 1;
-                while x2 > 0 && row[x2] < tree_height {
-                    x2 -= 1;
+            for (x, (stack, steps)) in stacks.iter_mut().enumerate().take(width) {
+                let starts_line = i == 0 || (dx < 0 && x == 0) || (dx > 0 && x == width - 1);
+                if starts_line {
+                    stack.clear();
+                    *steps = 0;
                 }
+                let index = y * width + x;
+                scores[index] *= view_back(stack, *steps, forest.heights[index]) as u128;
+                *steps += 1;
             }
-            score *= x - min(x2, n - 1);
-            // right
-            x2 = x + 1;
-            while x2 < n && row[x2] < tree_height {
-                x2 += 1;
+        }
+    }
+    scores
+}
+
+fn part2(forest: &Forest, directions: DirectionSet) -> u128 {
+    let scores = scenic_scores(forest, directions);
+    scores.into_iter().max().unwrap_or(0)
+}
+
+// Renders which directions each tree is visible from, as hex digits of its
+// visibility flags (or dots when hidden). With ANSI colours the tree heights
+// are shown instead, in green when visible.
+fn render_visibility(
+    forest: &Forest,
+    visible: &[u8],
+    directions: DirectionSet,
+    ansi: bool,
+) -> String {
+    let digits = directions.directions().len().div_ceil(4);
+    let mut res = String::new();
+    for (row, flags) in forest
+        .heights
+        .chunks(forest.width.max(1))
+        .zip(visible.chunks(forest.width.max(1)))
+    {
+        for (&tree_height, &from) in row.iter().zip(flags) {
+            match (ansi, from) {
+                (true, 0) => res += &format!("\x1b[2m{tree_height}\x1b[0m"),
+                (true, _) => res += &format!("\x1b[32m{tree_height}\x1b[0m"),
+                (false, 0) => res += &".".repeat(digits),
+                (false, _) => res += &format!("{from:0digits$x}"),
             }
-            score *= min(x2, n - 1) - x;
-            // update max
-            if score > max_score {
-                max_score = score;
+        }
+        res.push('\n');
+    }
+    res
+}
+
+// Renders scenic scores as a heatmap, scaled to the highest score. With ANSI
+// colours each cell is also shaded from blue (low) to red (high).
+fn render_scores(width: usize, scores: &[u128], ansi: bool) -> String {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+    let max_score = scores.iter().copied().max().unwrap_or(0).max(1);
+    let mut res = String::new();
+    for row in scores.chunks(width.max(1)) {
+        for &score in row {
+            let level = score * (RAMP.len() as u128 - 1) / max_score;
+            let c = RAMP[level as usize] as char;
+            if ansi {
+                let red = score * 255 / max_score;
+                res += &format!("\x1b[48;2;{red};0;{}m{c}\x1b[0m", 255 - red);
+            } else {
+                res.push(c);
             }
         }
+        res.push('\n');
+    }
+    res
+}
+
+// A plain (P2) PGM image of the values, scaled so the highest is white.
+fn to_pgm(width: usize, height: usize, values: &[u128]) -> String {
+    let max_value = values.iter().copied().max().unwrap_or(0).max(1);
+    let mut res = format!("P2\n{width} {height}\n255\n");
+    for row in values.chunks(width.max(1)) {
+        let pixels: Vec<String> = row
+            .iter()
+            .map(|value| (value * 255 / max_value).to_string())
+            .collect();
+        res += &pixels.join(" ");
+        res.push('\n');
+    }
+    res
+}
+
+// Generates a random forest of digits using a simple LCG, about one tree in
+// 50 being a 9. With only ten heights no view is ever long (a tree's view
+// ends at the next tree at least as tall), so brute force stays close to
+// linear on forests like the puzzle's.
+fn generate_forest(width: usize, height: usize, seed: u64) -> Forest {
+    let mut rng = Lcg::new(seed);
+    let heights = (0..width * height)
+        .map(|_| match rng.next(450) {
+            n if n < 9 => 9,
+            n => (n % 9) as u8,
+        })
+        .collect();
+    Forest {
+        heights,
+        width,
+        height,
+    }
+}
+
+// Generates a forest of long ramps, every tree taller than the 254 before it
+// along its row, column and diagonal. Looking back down a ramp sees all the
+// way to its foot, which is the expensive case for brute force.
+fn ramp_forest(width: usize, height: usize) -> Forest {
+    let heights = (0..height)
+        .flat_map(|y| (0..width).map(move |x| ((x + y) % 255) as u8))
+        .collect();
+    Forest {
+        heights,
+        width,
+        height,
+    }
+}
+
+// Times both scenic score implementations on a random and an adversarial
+// forest, checking that they agree.
+fn bench(width: usize, height: usize, directions: DirectionSet) {
+    let forests = [
+        ("random", generate_forest(width, height, 2022)),
+        ("ramps", ramp_forest(width, height)),
+    ];
+    for (name, forest) in forests.iter() {
+        let start = Instant::now();
+        let brute = scenic_scores_brute(forest, directions);
+        println!("{name}: brute force: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let fast = scenic_scores(forest, directions);
+        println!("{name}: monotonic stack: {:?}", start.elapsed());
+
+        assert_eq!(brute, fast, "scenic scores differ");
+        println!(
+            "{name}: max scenic score = {:?}",
+            fast.into_iter().max().unwrap_or(0)
+        );
     }
-    max_score
 }
 
+// Usage: day8 [--directions <cardinal|diagonal|all>] [--check]
+//             [--visibility] [--scores] [--ansi]
+//             [--pgm-visibility <path>] [--pgm-scores <path>]
+//        day8 [--directions <cardinal|diagonal|all>] --bench <width> <height>
 fn main() {
+    let mut directions = DirectionSet::Cardinal;
+    let mut check = false;
+    let mut show_visibility = false;
+    let mut show_scores = false;
+    let mut ansi = false;
+    let mut pgm_visibility = None;
+    let mut pgm_scores = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--directions" => {
+                directions = match args.next().as_deref() {
+                    Some("cardinal") => DirectionSet::Cardinal,
+                    Some("diagonal") => DirectionSet::Diagonal,
+                    Some("all") => DirectionSet::All,
+                    _ => panic!("--directions <cardinal|diagonal|all>"),
+                }
+            }
+            "--check" => check = true,
+            "--visibility" => show_visibility = true,
+            "--scores" => show_scores = true,
+            "--ansi" => ansi = true,
+            "--pgm-visibility" => {
+                pgm_visibility = Some(args.next().expect("--pgm-visibility <path>"))
+            }
+            "--pgm-scores" => pgm_scores = Some(args.next().expect("--pgm-scores <path>")),
+            "--bench" => {
+                let usage = "--bench <width> <height>";
+                let mut arg = || args.next().and_then(|n| n.parse().ok()).expect(usage);
+                let (width, height) = (arg(), arg());
+                return bench(width, height, directions);
+            }
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+
     let input: Vec<String> = read_input();
-    println!("part 1 = {:?}", part1(&input));
-    println!("part 2 = {:?}", part2(&input));
+    let forest = parse_forest(&input).unwrap();
+    if check {
+        let brute = scenic_scores_brute(&forest, directions);
+        assert_eq!(brute, scenic_scores(&forest, directions));
+    }
+    let Forest { width, height, .. } = forest;
+    if show_visibility || pgm_visibility.is_some() {
+        let visible = visibility(&forest, directions);
+        if show_visibility {
+            print!("{}", render_visibility(&forest, &visible, directions, ansi));
+        }
+        if let Some(path) = pgm_visibility {
+            // brighter trees are visible from more directions
+            let counts: Vec<u128> = visible.iter().map(|v| v.count_ones() as u128).collect();
+            fs::write(path, to_pgm(width, height, &counts)).unwrap();
+        }
+    }
+    if show_scores || pgm_scores.is_some() {
+        let scores = scenic_scores(&forest, directions);
+        if show_scores {
+            print!("{}", render_scores(width, &scores, ansi));
+        }
+        if let Some(path) = pgm_scores {
+            fs::write(path, to_pgm(width, height, &scores)).unwrap();
+        }
+    }
+    println!("part 1 = {:?}", part1(&forest, directions));
+    println!("part 2 = {:?}", part2(&forest, directions));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forest(rows: &[&str]) -> Forest {
+        let input: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+        parse_forest(&input).unwrap()
+    }
+
+    #[test]
+    fn scenic_scores_match_brute_force() {
+        let forests = [
+            // the puzzle's example
+            forest(&["30373", "25512", "65332", "33549", "35390"]),
+            // wider than tall, and taller than wide
+            forest(&["3037325", "2551265", "6533233"]),
+            forest(&["303", "255", "653", "335", "353", "912"]),
+            generate_forest(13, 4, 2022),
+            ramp_forest(3, 11),
+        ];
+        for forest in forests.iter() {
+            for directions in [
+                DirectionSet::Cardinal,
+                DirectionSet::Diagonal,
+                DirectionSet::All,
+            ] {
+                assert_eq!(
+                    scenic_scores(forest, directions),
+                    scenic_scores_brute(forest, directions),
+                    "{}x{} forest, {directions:?} directions",
+                    forest.width,
+                    forest.height
+                );
+            }
+        }
+        assert_eq!(part2(&forests[0], DirectionSet::Cardinal), 8);
+    }
 }
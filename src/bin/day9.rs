@@ -1,107 +1,436 @@
-#![feature(const_trait_impl)]
-
 use aoc::io::*;
+use aoc::rand::Lcg;
+use aoc::result::*;
 use std::collections::HashSet;
+use std::fmt;
 use std::ops::{Add, Sub};
+use std::time::Instant;
 
+// A position in D dimensions.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct Pos(i32, i32);
+struct Pos<const D: usize>([i32; D]);
 
-impl const Add for Pos {
-    type Output = Pos;
+impl<const D: usize> Add for Pos<D> {
+    type Output = Pos<D>;
 
     fn add(self, other: Self) -> Self::Output {
-        let Pos(x1, y1) = self;
-        let Pos(x2, y2) = other;
-        Self(x1 + x2, y1 + y2)
+        let Pos(mut a) = self;
+        let Pos(b) = other;
+        for i in 0..D {
+            a[i] += b[i];
+        }
+        Pos(a)
     }
 }
 
-impl const Sub for Pos {
-    type Output = Pos;
+impl<const D: usize> Sub for Pos<D> {
+    type Output = Pos<D>;
 
     fn sub(self, other: Self) -> Self::Output {
-        let Pos(x1, y1) = self;
-        let Pos(x2, y2) = other;
-        Self(x1 - x2, y1 - y2)
-    }
-}
-
-const UP: Pos = Pos(0, 1);
-const DOWN: Pos = Pos(0, -1);
-const LEFT: Pos = Pos(-1, 0);
-const RIGHT: Pos = Pos(1, 0);
-const UP_RIGHT: Pos = UP + RIGHT;
-const UP_LEFT: Pos = UP + LEFT;
-const DOWN_RIGHT: Pos = DOWN + RIGHT;
-const DOWN_LEFT: Pos = DOWN + LEFT;
-
-struct Rope {
-    knots: Vec<Pos>,
-}
-
-impl Rope {
-    fn new(n: usize) -> Rope {
-        let knots = vec![Pos(0, 0); n];
-        Rope { knots }
-    }
-
-    fn move_tail(&mut self, i: usize) {
-        if i > 0 {
-            let dxy = self.knots[i - 1] - self.knots[i];
-            let next_move = match dxy {
-                // simple moves
-                Pos(0, -2) => Some(UP),
-                Pos(0, 2) => Some(DOWN),
-                Pos(2, 0) => Some(LEFT),
-                Pos(-2, 0) => Some(RIGHT),
-                // diagonals
-                Pos(-1, -2) | Pos(-2, -1) | Pos(-2, -2) => Some(UP_RIGHT),
-                Pos(-1, 2) | Pos(-2, 1) | Pos(-2, 2) => Some(DOWN_RIGHT),
-                Pos(1, -2) | Pos(2, -1) | Pos(2, -2) => Some(UP_LEFT),
-                Pos(1, 2) | Pos(2, 1) | Pos(2, 2) => Some(DOWN_LEFT),
-                _ => None,
-            };
-            if let Some(a_move) = next_move {
-                self.move_pos(i - 1, a_move);
-            }
+        let Pos(mut a) = self;
+        let Pos(b) = other;
+        for i in 0..D {
+            a[i] -= b[i];
         }
+        Pos(a)
     }
+}
+
+impl<const D: usize> Pos<D> {
+    const ORIGIN: Pos<D> = Pos([0; D]);
 
-    fn move_pos(&mut self, i: usize, dir: Pos) {
-        self.knots[i] = self.knots[i] + dir;
-        self.move_tail(i);
+    // A unit step along the given axis, in the direction of sign.
+    fn unit(axis: usize, sign: i32) -> Pos<D> {
+        let mut pos = Self::ORIGIN;
+        pos.0[axis] = sign;
+        pos
     }
 
-    fn move_head(&mut self, dir: Pos) {
-        let head = self.knots.len() - 1;
-        self.move_pos(head, dir)
+    fn signum(self) -> Pos<D> {
+        Pos(self.0.map(i32::signum))
+    }
+
+    // The largest distance along any axis (the Chebyshev distance) from the
+    // origin.
+    fn max_norm(self) -> i32 {
+        self.0.iter().map(|n| n.abs()).max().unwrap_or(0)
+    }
+}
+
+// The axis and sign of each direction letter: R/L along the x axis, U/D
+// along the y axis and F/B along the z axis.
+const DIRECTIONS: [(char, usize, i32); 6] = [
+    ('U', 1, 1),
+    ('D', 1, -1),
+    ('R', 0, 1),
+    ('L', 0, -1),
+    ('F', 2, 1),
+    ('B', 2, -1),
+];
+
+// Parses a direction made of one or more letters along different axes, so
+// `UL` is a diagonal step up and to the left.
+fn direction<const D: usize>(name: &str) -> Option<Pos<D>> {
+    let mut dir = Pos::ORIGIN;
+    for c in name.chars() {
+        let &(_, axis, sign) = DIRECTIONS.iter().find(|(letter, _, _)| *letter == c)?;
+        if axis >= D || dir.0[axis] != 0 {
+            return None;
+        }
+        dir = dir + Pos::unit(axis, sign);
+    }
+    if dir == Pos::ORIGIN {
+        None
+    } else {
+        Some(dir)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Motion<const D: usize> {
+    dir: Pos<D>,
+    steps: u32,
+}
+
+impl<const D: usize> fmt::Display for Motion<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (letter, axis, sign) in DIRECTIONS {
+            if axis < D && self.dir.0[axis] == sign {
+                write!(f, "{letter}")?;
+            }
+        }
+        write!(f, " {}", self.steps)
     }
 }
 
-fn unique_tail_positions(input: &Vec<String>, n: usize) -> usize {
-    let mut rope = Rope::new(n);
-    let mut tail_positions = HashSet::<Pos>::new();
-    for line in input.iter() {
-        let parts: Vec<&str> = line.as_str().split(" ").collect();
-        let steps = parts[1].parse().unwrap();
-        let dir = match parts[0] {
-            "R" => RIGHT,
-            "L" => LEFT,
-            "U" => UP,
-            "D" => DOWN,
-            _ => panic!(),
+// Parses one motion per line, e.g. `R 4` or `UL 2`. Blank lines are skipped.
+fn parse_motions<const D: usize>(input: &[String]) -> AocResult<Vec<Motion<D>>> {
+    let mut motions = Vec::with_capacity(input.len());
+    for (i, line) in input.iter().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let motion = match parts[..] {
+            [] => continue,
+            [dir, steps] => match (direction(dir), steps.parse()) {
+                (Some(dir), Ok(steps)) => Motion { dir, steps },
+                (None, _) => {
+                    let expected = format!("a direction made of {{U,D,R,L,F,B}} in {D}D");
+                    return parse_error_at(i + 1, line.to_string(), expected);
+                }
+                (_, Err(_)) => {
+                    let expected = "a non-negative number of steps".into();
+                    return parse_error_at(i + 1, line.to_string(), expected);
+                }
+            },
+            _ => return parse_error_at(i + 1, line.to_string(), "<direction> <steps>".into()),
         };
-        for _ in 0..steps {
-            rope.move_head(dir);
-            tail_positions.insert(rope.knots[0]);
+        motions.push(motion);
+    }
+    Ok(motions)
+}
+
+struct Rope<const D: usize> {
+    // the knots, from the head (first) to the tail (last)
+    knots: Vec<Pos<D>>,
+    // how far (along any axis) a knot may get from the knot it follows
+    // before it has to move
+    slack: i32,
+}
+
+impl<const D: usize> Rope<D> {
+    fn new(n: usize, slack: i32) -> Rope<D> {
+        assert!(n >= 1, "a rope needs at least one knot");
+        assert!(slack >= 0, "a rope can't have negative slack");
+        Rope {
+            knots: vec![Pos::ORIGIN; n],
+            slack,
+        }
+    }
+
+    fn tail(&self) -> Pos<D> {
+        self.knots[self.knots.len() - 1]
+    }
+
+    // Moves the head one step, then pulls each following knot one step
+    // towards the knot in front of it (along every axis they differ on) if
+    // it's too far behind. Once a knot stays put so does the rest of the
+    // rope. Returns the number of knots that moved.
+    fn move_head(&mut self, dir: Pos<D>) -> usize {
+        self.knots[0] = self.knots[0] + dir;
+        for i in 1..self.knots.len() {
+            let delta = self.knots[i - 1] - self.knots[i];
+            if delta.max_norm() <= self.slack {
+                return i;
+            }
+            self.knots[i] = self.knots[i] + delta.signum();
+        }
+        self.knots.len()
+    }
+}
+
+// The outcome of moving a rope through a series of motions.
+struct Simulation<const D: usize> {
+    // the cells each knot has visited, from the head to the tail
+    visited: Vec<HashSet<Pos<D>>>,
+    // the number of steps each knot has taken
+    path_lengths: Vec<u64>,
+    // the smallest box containing every position of every knot
+    bounds: (Pos<D>, Pos<D>),
+    // if asked for, the position of each knot at the start and after every
+    // step, from the head to the tail
+    history: Option<Vec<Vec<Pos<D>>>>,
+}
+
+impl<const D: usize> Simulation<D> {
+    fn unique_tail_positions(&self) -> usize {
+        self.visited[self.visited.len() - 1].len()
+    }
+}
+
+fn simulate<const D: usize>(
+    motions: &[Motion<D>],
+    n: usize,
+    slack: i32,
+    record_history: bool,
+) -> Simulation<D> {
+    let mut rope = Rope::<D>::new(n, slack);
+    let mut sim = Simulation {
+        visited: vec![HashSet::from([Pos::ORIGIN]); n],
+        path_lengths: vec![0; n],
+        bounds: (Pos::ORIGIN, Pos::ORIGIN),
+        history: record_history.then(|| vec![vec![Pos::ORIGIN]; n]),
+    };
+    for motion in motions.iter() {
+        for _ in 0..motion.steps {
+            // only the knots that moved need updating
+            let moved = rope.move_head(motion.dir);
+            for (i, &knot) in rope.knots[..moved].iter().enumerate() {
+                sim.visited[i].insert(knot);
+                sim.path_lengths[i] += 1;
+                sim.bounds = bounds([sim.bounds.0, sim.bounds.1, knot].iter());
+            }
+            if let Some(history) = sim.history.as_mut() {
+                for (positions, &knot) in history.iter_mut().zip(rope.knots.iter()) {
+                    positions.push(knot);
+                }
+            }
+        }
+    }
+    sim
+}
+
+// The smallest box containing the origin and all the given positions.
+fn bounds<'a, const D: usize>(positions: impl Iterator<Item = &'a Pos<D>>) -> (Pos<D>, Pos<D>) {
+    positions.fold((Pos::ORIGIN, Pos::ORIGIN), |(mut min, mut max), pos| {
+        for axis in 0..D {
+            min.0[axis] = min.0[axis].min(pos.0[axis]);
+            max.0[axis] = max.0[axis].max(pos.0[axis]);
+        }
+        (min, max)
+    })
+}
+
+fn print_stats<const D: usize>(sim: &Simulation<D>) {
+    println!("knot  visited  path length");
+    for (i, visited) in sim.visited.iter().enumerate() {
+        println!("{i:>4}  {:>7}  {:>11}", visited.len(), sim.path_lengths[i]);
+    }
+    let (min, max) = sim.bounds;
+    println!("bounds: {:?} to {:?}", min.0, max.0);
+}
+
+// Renders the rope within the given bounds like the puzzle does: H for the
+// head followed by 1..9 (or T for the tail of a two knot rope), s for the
+// start and # for the cells the tail has visited. Knots closer to the head
+// are drawn over the ones behind them, and y grows upwards.
+fn render(knots: &[Pos<2>], visited: &HashSet<Pos<2>>, (min, max): (Pos<2>, Pos<2>)) -> String {
+    let label = |i: usize| match i {
+        0 => 'H',
+        1 if knots.len() == 2 => 'T',
+        1..=9 => char::from_digit(i as u32, 10).unwrap(),
+        _ => '+',
+    };
+    let mut res = String::new();
+    for y in (min.0[1]..=max.0[1]).rev() {
+        for x in min.0[0]..=max.0[0] {
+            let pos = Pos([x, y]);
+            let c = match knots.iter().position(|&knot| knot == pos) {
+                Some(i) => label(i),
+                None if pos == Pos::ORIGIN => 's',
+                None if visited.contains(&pos) => '#',
+                None => '.',
+            };
+            res.push(c);
+        }
+        res.push('\n');
+    }
+    res
+}
+
+// Prints the rope after every motion, or after every step of each motion,
+// in a frame sized to fit the whole simulation.
+fn print_frames(motions: &[Motion<2>], n: usize, slack: i32, every_step: bool) {
+    let sim = simulate(motions, n, slack, true);
+    let history = sim.history.expect("history is recorded");
+    let knots_at =
+        |step: usize| -> Vec<Pos<2>> { history.iter().map(|positions| positions[step]).collect() };
+
+    let mut step = 0;
+    let mut visited = HashSet::from([Pos::ORIGIN]);
+    println!(
+        "== Initial State ==\n\n{}",
+        render(&knots_at(step), &visited, sim.bounds)
+    );
+    for motion in motions.iter() {
+        println!("== {motion} ==\n");
+        for _ in 0..motion.steps {
+            step += 1;
+            visited.insert(history[n - 1][step]);
+            if every_step {
+                println!("{}", render(&knots_at(step), &visited, sim.bounds));
+            }
+        }
+        if !every_step {
+            println!("{}", render(&knots_at(step), &visited, sim.bounds));
+        }
+    }
+}
+
+// Times a long rope following randomly generated moves (from a simple LCG)
+// of up to 10 steps each.
+fn bench(n: usize, moves: usize) {
+    let mut rng = Lcg::new(2022);
+    let motions: Vec<Motion<2>> = (0..moves)
+        .map(|_| Motion {
+            dir: direction(["R", "L", "U", "D"][rng.next(4) as usize]).unwrap(),
+            steps: 1 + rng.next(10) as u32,
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut rope = Rope::<2>::new(n, 1);
+    let mut tail_positions = HashSet::new();
+    let mut steps = 0;
+    for motion in motions.iter() {
+        for _ in 0..motion.steps {
+            rope.move_head(motion.dir);
+            tail_positions.insert(rope.tail());
         }
+        steps += motion.steps as u64;
     }
-    tail_positions.len()
+    let elapsed = start.elapsed();
+    println!(
+        "{} knots, {} moves ({steps} steps): {elapsed:?}",
+        rope.knots.len(),
+        motions.len()
+    );
+    println!("unique tail positions = {}", tail_positions.len());
 }
 
+fn solve<const D: usize>(input: &[String], slack: i32, show_stats: bool) -> AocResult<()> {
+    let motions = parse_motions::<D>(input)?;
+    for (part, n) in [(1, 2), (2, 10)] {
+        let sim = simulate(&motions, n, slack, false);
+        println!("part {part} = {:?}", sim.unique_tail_positions());
+        if show_stats {
+            print_stats(&sim);
+        }
+    }
+    Ok(())
+}
+
+// Usage: day9 [--slack <n>] [--3d] [--stats]
+//        day9 [--slack <n>] --frames <knots> [--every-step]
+//        day9 --bench <knots> <moves>
 fn main() {
+    let mut slack = 1;
+    let mut three_dimensional = false;
+    let mut frames = None;
+    let mut every_step = false;
+    let mut show_stats = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slack" => {
+                slack = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n: &i32| n >= 0)
+                    .expect("--slack <n>, with n at least 0")
+            }
+            "--3d" => three_dimensional = true,
+            "--frames" => {
+                // the frames label the tail apart from the head
+                let knots = args.next().and_then(|n| n.parse().ok());
+                let knots = knots.filter(|&n: &usize| n >= 2);
+                frames = Some(knots.expect("--frames <knots>, with at least 2 knots"));
+            }
+            "--every-step" => every_step = true,
+            "--stats" => show_stats = true,
+            "--bench" => {
+                let usage = "--bench <knots> <moves>, with at least 1 knot";
+                let mut arg = || args.next().and_then(|n| n.parse().ok()).expect(usage);
+                let (n, moves) = (arg(), arg());
+                assert!(n >= 1, "{usage}");
+                return bench(n, moves);
+            }
+            _ => panic!(
+                "unknown argument {arg}, expected --slack, --3d, --stats, --frames, --every-step or --bench"
+            ),
+        }
+    }
+
     let input: Vec<String> = read_input();
-    println!("part 1 = {:?}", unique_tail_positions(&input, 2));
-    println!("part 2 = {:?}", unique_tail_positions(&input, 10));
+    if let Some(n) = frames {
+        return print_frames(&parse_motions(&input).unwrap(), n, slack, every_step);
+    }
+    if three_dimensional {
+        solve::<3>(&input, slack, show_stats).unwrap();
+    } else {
+        solve::<2>(&input, slack, show_stats).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The puzzle's frame for the first motions of its example, with the cells
+    // the tail has visited marked too.
+    #[test]
+    fn render_matches_puzzle_frames() {
+        let input = ["R 4".to_string(), "U 4".to_string()];
+        let motions = parse_motions::<2>(&input).unwrap();
+        let history = simulate(&motions, 2, 1, true).history.unwrap();
+        let frame = (Pos([0, 0]), Pos([5, 4]));
+        let render_step = |step: usize| {
+            let knots = [history[0][step], history[1][step]];
+            let visited = history[1][..=step].iter().copied().collect();
+            render(&knots, &visited, frame)
+        };
+
+        assert_eq!(
+            render_step(0),
+            "......\n\
+             ......\n\
+             ......\n\
+             ......\n\
+             H.....\n"
+        );
+        assert_eq!(
+            render_step(4),
+            "......\n\
+             ......\n\
+             ......\n\
+             ......\n\
+             s##TH.\n"
+        );
+        assert_eq!(
+            render_step(8),
+            "....H.\n\
+             ....T.\n\
+             ....#.\n\
+             ....#.\n\
+             s###..\n"
+        );
+    }
 }
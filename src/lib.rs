@@ -3,13 +3,29 @@ pub mod result {
 
     #[derive(Debug)]
     pub enum AocError {
-        ParseError { got: String, expected: String },
+        ParseError {
+            got: String,
+            expected: String,
+        },
+        ParseErrorAt {
+            line: usize,
+            got: String,
+            expected: String,
+        },
     }
 
     pub fn parse_error<T>(got: String, expected: String) -> AocResult<T> {
         Err(AocError::ParseError { got, expected })
     }
 
+    pub fn parse_error_at<T>(line: usize, got: String, expected: String) -> AocResult<T> {
+        Err(AocError::ParseErrorAt {
+            line,
+            got,
+            expected,
+        })
+    }
+
     pub type AocResult<T> = Result<T, AocError>;
 
     pub trait AocResultT<T> {
@@ -42,6 +58,27 @@ pub mod result {
     }
 }
 
+pub mod rand {
+    // A simple linear congruential generator, for repeatable generated inputs.
+    pub struct Lcg(u64);
+
+    impl Lcg {
+        pub fn new(seed: u64) -> Lcg {
+            Lcg(seed)
+        }
+
+        // The next number below bound, taken from the high bits (which are
+        // the random ones) so bound should stay below 2^31.
+        pub fn next(&mut self, bound: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % bound
+        }
+    }
+}
+
 pub mod io {
     use std::io;
     use std::io::BufRead;